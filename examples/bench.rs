@@ -1,6 +1,12 @@
 #![feature(lazy_get)]
 #![allow(unused)]
-use std::{any::type_name, cell::Cell, collections::HashMap, path::PathBuf, sync::LazyLock};
+use std::{
+    any::type_name,
+    cell::Cell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
 
 use clap::Parser;
 use packed_seq::{AsciiSeqVec, PackedSeqVec, SeqVec};
@@ -12,10 +18,42 @@ use uindex::{
     indices::{DivSufSortSa, FmAwryParams, FmSdslParams, LibSaisSa},
     s_index::SIndex,
     sketchers::{IdentityParams, MinimizerParams},
-    utils::{read_chromosomes, Timer, INIT_TRACE},
+    utils::{
+        read_chromosomes, timer_profile_dot, timer_profile_folded, write_resource_csv,
+        write_stats_json, Stats, Timer, INIT_TRACE,
+    },
     IndexBuilder, SketcherBuilder, UIndex,
 };
 
+/// How often the `ResourceSampler` background thread samples RSS/CPU.
+const RESOURCE_SAMPLE_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Selects which [`SeqVec`] alphabet `--text` is read as.
+#[derive(Clone, Copy, Debug)]
+enum SeqType {
+    /// 2-bit packed DNA (`PackedSeqVec`).
+    Packed,
+    /// 1 byte per base, no packing (`AsciiSeqVec`).
+    Ascii,
+    /// Raw bytes, no alphabet restriction (`Vec<u8>`).
+    Bytes,
+}
+
+impl std::str::FromStr for SeqType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dna" | "packed" => Ok(SeqType::Packed),
+            "ascii" => Ok(SeqType::Ascii),
+            "text" | "bytes" => Ok(SeqType::Bytes),
+            _ => Err(format!(
+                "Unknown --seq-type {s:?}, expected one of dna, packed, ascii, text, bytes"
+            )),
+        }
+    }
+}
+
 #[derive(Parser)]
 struct Args {
     /// Run experiments in subprocesses.
@@ -34,6 +72,31 @@ struct Args {
     #[clap(long)]
     output: Option<PathBuf>,
 
+    /// Persist each built index under this directory (keyed by run id) and
+    /// reload it on a later run instead of rebuilding from scratch. Mainly
+    /// useful with `--ext`, where each `(sketcher, index)` combination is
+    /// otherwise rebuilt from scratch in its own subprocess.
+    #[clap(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// Alphabet to read `--text` as: dna/packed, ascii, or text/bytes.
+    #[clap(long, default_value = "bytes")]
+    seq_type: SeqType,
+
+    /// Write the Timer hierarchy (build vs. query phases for every
+    /// (sketcher, index) combination run so far) as folded-stack output to
+    /// this path, and as a Graphviz digraph to the same path with a `.dot`
+    /// extension.
+    #[clap(long)]
+    timing_output: Option<PathBuf>,
+
+    /// Number of threads for the parallel query-throughput benchmark
+    /// (`bench_parallel`), run alongside the single-threaded `bench` used
+    /// for `rss`/correctness stats. Defaults to the number of available
+    /// cores.
+    #[clap(long)]
+    query_threads: Option<usize>,
+
     #[clap(short)]
     k: Option<usize>,
     #[clap(short)]
@@ -66,23 +129,43 @@ fn main() {
     }
 
     if let Some(text) = &ARGS.text {
-        let seq = std::fs::read(text).unwrap();
+        let raw = std::fs::read(text).unwrap();
 
         let pattern_data = std::fs::read(ARGS.patterns.as_ref().unwrap()).unwrap();
-        let queries = pattern_data
+        let patterns = pattern_data
             .split(|&c| c == b'\n')
             .map(|x| x.to_vec())
             .collect::<Vec<_>>();
         let query_length = 0;
 
         let mut all_stats = vec![];
-        run::<Vec<u8>>(&mut all_stats, &seq, query_length, &queries, kls);
+        match ARGS.seq_type {
+            SeqType::Packed => {
+                let seq = PackedSeqVec::from_ascii(&raw);
+                let queries = patterns
+                    .iter()
+                    .map(|p| PackedSeqVec::from_ascii(p))
+                    .collect::<Vec<_>>();
+                run::<PackedSeqVec>(&mut all_stats, &seq, query_length, &queries, kls);
+            }
+            SeqType::Ascii => {
+                let seq = AsciiSeqVec::from_ascii(&raw);
+                let queries = patterns
+                    .iter()
+                    .map(|p| AsciiSeqVec::from_ascii(p))
+                    .collect::<Vec<_>>();
+                run::<AsciiSeqVec>(&mut all_stats, &seq, query_length, &queries, kls);
+            }
+            SeqType::Bytes => {
+                run::<Vec<u8>>(&mut all_stats, &raw, query_length, &patterns, kls);
+            }
+        }
 
-        let stats_string = serde_json::to_string(&all_stats).unwrap();
         let path = PathBuf::from("stats.json");
         let output = ARGS.output.as_ref().unwrap_or(&path);
-        std::fs::write(output, stats_string).unwrap();
+        write_stats_json(output, &all_stats).unwrap();
 
+        write_timing_output();
         return;
     }
 
@@ -98,8 +181,7 @@ fn main() {
 
         // Write all_stats.
         if ARGS.run_id.is_none() {
-            let stats_string = serde_json::to_string(&all_stats).unwrap();
-            std::fs::write("stats.json", stats_string).unwrap();
+            write_stats_json(Path::new("stats.json"), &all_stats).unwrap();
         }
     }
 
@@ -119,8 +201,7 @@ fn main() {
 
         // Write all_stats.
         if ARGS.run_id.is_none() {
-            let stats_string = serde_json::to_string(&all_stats).unwrap();
-            std::fs::write("stats-english.json", stats_string).unwrap();
+            write_stats_json(Path::new("stats-english.json"), &all_stats).unwrap();
         }
     }
 
@@ -140,17 +221,29 @@ fn main() {
 
         // Write all_stats.
         if ARGS.run_id.is_none() {
-            let stats_string = serde_json::to_string(&all_stats).unwrap();
-            std::fs::write("stats-proteins.json", stats_string).unwrap();
+            write_stats_json(Path::new("stats-proteins.json"), &all_stats).unwrap();
         }
     }
 
     if let Some(run_id) = ARGS.run_id {
         panic!("Did non find run id {run_id}");
     }
+
+    write_timing_output();
 }
 
-fn run<'s, SV: SeqVec>(
+/// If `--timing-output <path>` was given, write the accumulated `Timer`
+/// hierarchy as folded-stack output to `path` and as a Graphviz digraph to
+/// `path` with a `.dot` extension.
+fn write_timing_output() {
+    let Some(path) = &ARGS.timing_output else {
+        return;
+    };
+    std::fs::write(path, timer_profile_folded()).unwrap();
+    std::fs::write(path.with_extension("dot"), timer_profile_dot()).unwrap();
+}
+
+fn run<'s, SV: SeqVec + Sync>(
     all_stats: &mut Vec<HashMap<&str, Value>>,
     seq: &'s SV,
     query_length: usize,
@@ -202,8 +295,14 @@ fn run<'s, SV: SeqVec>(
             store_ms_seq: false,
             par: false,
         };
-        let awry32 = &FmAwryParams { sa_sampling: 32 };
-        let awry64 = &FmAwryParams { sa_sampling: 64 };
+        let awry32 = &FmAwryParams {
+            sa_sampling: 32,
+            scratch_dir: std::env::temp_dir(),
+        };
+        let awry64 = &FmAwryParams {
+            sa_sampling: 64,
+            scratch_dir: std::env::temp_dir(),
+        };
         let sdsl_byte_32 = &FmSdslParams::<FmIndexByte32Ptr, _>::new();
         let sdsl_byte_64 = &FmSdslParams::<FmIndexByte64Ptr, _>::new();
         let sdsl_int_32 = &FmSdslParams::<FmIndexInt32Ptr, _>::new();
@@ -237,12 +336,41 @@ fn run<'s, SV: SeqVec>(
 
                 let rss0 = max_rss();
 
-                let u = UIndex::<SV>::try_build_with_ranges(&seq, &ranges, &*s, &*p)?;
+                let cache_dir = ARGS
+                    .cache_dir
+                    .as_ref()
+                    .map(|dir| dir.join(format!("{}", ID.get())));
+
+                let build_stats = Stats::default();
+                let u = {
+                    let _sampler = build_stats.sample_resources(RESOURCE_SAMPLE_INTERVAL);
+                    if let Some(cache_dir) = &cache_dir {
+                        match UIndex::<SV>::load(cache_dir, &seq, &*s, &*p) {
+                            Ok(u) => u,
+                            Err(_) => {
+                                let u = UIndex::<SV>::try_build_with_ranges(&seq, &ranges, &*s, &*p)?;
+                                if let Err(e) = u.save(cache_dir) {
+                                    tracing::warn!("Failed to cache UIndex to {cache_dir:?}: {e}");
+                                }
+                                u
+                            }
+                        }
+                    } else {
+                        UIndex::<SV>::try_build_with_ranges(&seq, &ranges, &*s, &*p)?
+                    }
+                };
                 let rss1 = max_rss();
+                let query_stats = Stats::default();
                 let query_time = {
                     let _t = Timer::new("bench_positive").info();
+                    let _sampler = query_stats.sample_resources(RESOURCE_SAMPLE_INTERVAL);
                     u.bench(queries)
                 };
+                let num_threads = query_threads();
+                let throughput = {
+                    let _t = Timer::new("bench_parallel").info();
+                    u.bench_parallel(queries, num_threads)
+                };
                 let rss2 = max_rss();
                 let mut stats = u.stats();
                 stats.insert("rss0", Value::Number(Number::from(rss0)));
@@ -255,6 +383,15 @@ fn run<'s, SV: SeqVec>(
                     "query_time",
                     Value::Number(Number::from_f64(query_time).unwrap()),
                 );
+                insert_throughput_stats(&mut stats, num_threads, throughput);
+                if let Some(samples) = build_stats.clone().into().remove("resource_samples") {
+                    let _ = write_resource_csv(&samples, &PathBuf::from("stats-build.csv"));
+                    stats.insert("build_resource_samples", samples);
+                }
+                if let Some(samples) = query_stats.clone().into().remove("resource_samples") {
+                    let _ = write_resource_csv(&samples, &PathBuf::from("stats-query.csv"));
+                    stats.insert("query_resource_samples", samples);
+                }
                 Some(stats)
             });
             if let Some(stats) = stats {
@@ -275,6 +412,11 @@ fn run<'s, SV: SeqVec>(
                     let _t = Timer::new("bench_positive").info();
                     u.bench(queries)
                 };
+                let num_threads = query_threads();
+                let throughput = {
+                    let _t = Timer::new("bench_parallel").info();
+                    u.bench_parallel(queries, num_threads)
+                };
                 let rss2 = max_rss();
                 let mut stats = u.stats();
                 stats.insert("rss0", Value::Number(Number::from(rss0)));
@@ -293,6 +435,7 @@ fn run<'s, SV: SeqVec>(
                     "query_time",
                     Value::Number(Number::from_f64(query_time).unwrap()),
                 );
+                insert_throughput_stats(&mut stats, num_threads, throughput);
                 Some(stats)
             });
             if let Some(stats) = stats {
@@ -309,6 +452,11 @@ fn run<'s, SV: SeqVec>(
                     let _t = Timer::new("bench_positive").info();
                     u.bench(&queries)
                 };
+                let num_threads = query_threads();
+                let throughput = {
+                    let _t = Timer::new("bench_parallel").info();
+                    u.bench_parallel(queries, num_threads)
+                };
                 let rss2 = max_rss();
                 let mut stats = u.stats();
                 stats.insert("rss0", Value::Number(Number::from(rss0)));
@@ -327,6 +475,7 @@ fn run<'s, SV: SeqVec>(
                     "query_time",
                     Value::Number(Number::from_f64(query_time).unwrap()),
                 );
+                insert_throughput_stats(&mut stats, num_threads, throughput);
                 Some(stats)
             });
             if let Some(stats) = stats {
@@ -347,6 +496,10 @@ fn run_fn(
                 let mut result = f();
                 if let Some(result) = result.as_mut() {
                     result.insert("id", Value::Number(Number::from(ID.get())));
+                    if let Err(e) = write_stats_json(Path::new(stats_path_for_id(ID.get())), std::slice::from_ref(result))
+                    {
+                        tracing::warn!("Failed to merge this run's result into stats.json: {e}");
+                    }
                 }
                 println!("{}", serde_json::to_string(&result).unwrap());
                 info!("RUNNING FOR ID {} DONE", ID.get());
@@ -380,6 +533,49 @@ fn run_fn(
     }
 }
 
+/// Which `stats*.json` file a given `ID` (as set by the `ID.set(...)` calls
+/// in `main()`) belongs to, so a worker invocation (`--ext --run-id N`) can
+/// merge its own result directly into the same file the orchestrator writes.
+fn stats_path_for_id(id: usize) -> &'static str {
+    match id {
+        0..1000 => "stats.json",
+        1000..2000 => "stats-english.json",
+        _ => "stats-proteins.json",
+    }
+}
+
+/// Threads for `bench_parallel`: `--query-threads N`, or all available cores.
+fn query_threads() -> usize {
+    ARGS.query_threads.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    })
+}
+
+/// Insert `query_threads`/`query_throughput`/`query_p50_latency`/
+/// `query_p99_latency` (seconds) from a [`uindex::QueryThroughput`] into
+/// `stats`, alongside the single-threaded `query_time` already there.
+fn insert_throughput_stats(
+    stats: &mut HashMap<&'static str, Value>,
+    num_threads: usize,
+    throughput: uindex::QueryThroughput,
+) {
+    stats.insert("query_threads", Value::Number(Number::from(num_threads)));
+    stats.insert(
+        "query_throughput",
+        Value::Number(Number::from_f64(throughput.queries_per_sec).unwrap()),
+    );
+    stats.insert(
+        "query_p50_latency",
+        Value::Number(Number::from_f64(throughput.p50_latency.as_secs_f64()).unwrap()),
+    );
+    stats.insert(
+        "query_p99_latency",
+        Value::Number(Number::from_f64(throughput.p99_latency.as_secs_f64()).unwrap()),
+    );
+}
+
 fn max_rss() -> usize {
     let rusage = unsafe {
         let mut rusage = std::mem::MaybeUninit::uninit();
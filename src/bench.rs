@@ -22,10 +22,12 @@ impl<'s, SV: SeqVec + 'static> UIndex<'s, SV> {
         let start = std::time::Instant::now();
 
         let mut num_matches = 0;
+        let mut out = Vec::new();
         let mut i = 0usize;
         for seq in queries {
-            if let Some(locate_it) = self.query(seq.as_slice()) {
-                num_matches += locate_it.count();
+            out.clear();
+            if let Some(cnt) = self.query_into(seq.as_slice(), &mut out) {
+                num_matches += cnt;
             }
             i += 1;
             if i.is_power_of_two() {
@@ -48,11 +50,13 @@ impl<'s, SV: SeqVec> SIndex<'s, SV> {
         let start = std::time::Instant::now();
 
         let mut num_matches = 0;
+        let mut out = Vec::new();
 
         let mut i = 0usize;
         for seq in queries {
-            if let Some(locate_it) = self.query(seq.as_slice()) {
-                num_matches += locate_it.count();
+            out.clear();
+            if let Some(cnt) = self.query_into(seq.as_slice(), &mut out) {
+                num_matches += cnt;
             }
             i += 1;
             if i.is_power_of_two() {
@@ -1,6 +1,7 @@
 //! TODO: FM-index:
 //! - faster-minuter
 //! - quad-wavelet-tree
+mod ac_index;
 #[cfg(feature = "awry")]
 mod fm_awry;
 #[cfg(feature = "bio")]
@@ -12,6 +13,7 @@ mod sa_divsufsort;
 mod sa_libsais;
 mod suffix_array;
 
+pub use ac_index::AcParams;
 #[cfg(feature = "awry")]
 pub use fm_awry::FmAwryParams;
 #[cfg(feature = "bio")]
@@ -0,0 +1,165 @@
+//! A multi-pattern index tuned for "many queries, one modest text", as
+//! opposed to the FM-index's "one query, huge text" trade-off: instead of
+//! running a backward search per pattern, build a single Aho-Corasick
+//! automaton over a whole batch of sketched patterns and scan the text once.
+use mem_dbg::MemSize;
+use packed_seq::SeqVec;
+use serde_json::Value;
+
+use crate::utils::Stats;
+use crate::{Index, IndexBuilder, Sketcher};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AcParams;
+
+/// Keeps the `MsSequence` bytes around in plain form, so an automaton can be
+/// built on demand for a batch of query patterns and scan them over the text.
+#[derive(MemSize)]
+pub struct AcIndex {
+    ms_seq: Vec<u8>,
+}
+
+impl<SV: SeqVec> IndexBuilder<SV> for AcParams {
+    fn build_with_stats(&self, ms_seq: Vec<u8>, _width: usize, stats: &Stats) -> Box<dyn Index<SV>> {
+        stats.set_val("index", Value::String("AC".to_string()));
+        stats.set("sequence_length", ms_seq.len());
+        Box::new(AcIndex { ms_seq })
+    }
+}
+
+impl<SV: SeqVec> Index<SV> for AcIndex {
+    fn query(
+        &self,
+        pattern: &[u8],
+        _seq: SV::Seq<'_>,
+        sketcher: &dyn Sketcher<SV>,
+    ) -> Box<dyn Iterator<Item = usize> + '_> {
+        let positions = self
+            .query_batch(&[pattern], sketcher)
+            .into_iter()
+            .map(|(_pattern_id, plain_pos)| plain_pos)
+            .collect::<Vec<_>>();
+        Box::new(positions.into_iter())
+    }
+
+    fn query_into(
+        &self,
+        pattern: &[u8],
+        _seq: SV::Seq<'_>,
+        sketcher: &dyn Sketcher<SV>,
+        out: &mut Vec<usize>,
+    ) -> usize {
+        let start = out.len();
+        out.extend(
+            self.query_batch(&[pattern], sketcher)
+                .into_iter()
+                .map(|(_pattern_id, plain_pos)| plain_pos),
+        );
+        out.len() - start
+    }
+}
+
+impl AcIndex {
+    /// Build an Aho-Corasick automaton over `patterns` (already sketched to
+    /// minimizer-space bytes) and scan the text once, reporting every
+    /// occurrence of every pattern as a `(pattern_id, plain_pos)` pair.
+    pub fn query_batch<SV: SeqVec>(
+        &self,
+        patterns: &[&[u8]],
+        sketcher: &dyn Sketcher<SV>,
+    ) -> Vec<(usize, usize)> {
+        let automaton = AhoCorasick::build(patterns);
+        automaton
+            .scan(&self.ms_seq)
+            .filter_map(|(pattern_id, end_pos)| {
+                let ms_pos = end_pos - patterns[pattern_id].len();
+                sketcher
+                    .ms_pos_to_plain_pos(ms_pos)
+                    .map(|plain_pos| (pattern_id, plain_pos))
+            })
+            .collect()
+    }
+}
+
+/// A minimal Aho-Corasick automaton over byte strings, built the textbook
+/// way: a trie with a goto table per node, failure links assigned by BFS
+/// (a node's failure is its parent-failure's goto on the same byte, falling
+/// back to the root) and then folded into `goto` itself so it becomes a
+/// total DFA, and each node's output set unioned with its failure target's
+/// outputs.
+struct AhoCorasick {
+    /// `goto[node][byte]` is the next node; completed into a full DFA by
+    /// `build`, so this is always a valid node index, never `usize::MAX`.
+    goto: Vec<[usize; 256]>,
+    /// Pattern ids that end at this node (after following failure links).
+    output: Vec<Vec<usize>>,
+}
+
+const ROOT: usize = 0;
+
+impl AhoCorasick {
+    fn build(patterns: &[&[u8]]) -> Self {
+        let mut goto = vec![[usize::MAX; 256]];
+        let mut output = vec![Vec::new()];
+
+        for (id, pattern) in patterns.iter().enumerate() {
+            let mut node = ROOT;
+            for &byte in pattern.iter() {
+                node = match goto[node][byte as usize] {
+                    usize::MAX => {
+                        goto.push([usize::MAX; 256]);
+                        output.push(Vec::new());
+                        let child = goto.len() - 1;
+                        goto[node][byte as usize] = child;
+                        child
+                    }
+                    child => child,
+                };
+            }
+            output[node].push(id);
+        }
+
+        let mut fail = vec![ROOT; goto.len()];
+        let mut queue = std::collections::VecDeque::new();
+        for byte in 0..256 {
+            let child = goto[ROOT][byte];
+            if child != usize::MAX {
+                fail[child] = ROOT;
+                queue.push_back(child);
+            } else {
+                goto[ROOT][byte] = ROOT;
+            }
+        }
+        // Complete `goto` into a full DFA as we go: by the time `node` is
+        // dequeued, `goto[fail[node]]` is already fully completed (`fail[node]`
+        // is strictly shallower than `node`, and the root's row was completed
+        // above), so every lookup below is resolved, never `usize::MAX`.
+        while let Some(node) = queue.pop_front() {
+            for byte in 0..256 {
+                let child = goto[node][byte];
+                if child == usize::MAX {
+                    goto[node][byte] = goto[fail[node]][byte];
+                    continue;
+                }
+                fail[child] = goto[fail[node]][byte];
+                let fail_output = output[fail[child]].clone();
+                output[child].extend(fail_output);
+                queue.push_back(child);
+            }
+        }
+
+        Self { goto, output }
+    }
+
+    /// Walk `text` once, yielding `(pattern_id, end_pos)` for every pattern
+    /// occurrence found, where `end_pos` is one past the last matched byte.
+    fn scan<'a>(&'a self, text: &'a [u8]) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let mut node = ROOT;
+        text.iter().enumerate().flat_map(move |(i, &byte)| {
+            // `goto` is a completed DFA (see `build`), so every transition
+            // is defined and no failure-link walk is needed here.
+            node = self.goto[node][byte as usize];
+            self.output[node].iter().map(move |&id| (id, i + 1))
+        })
+    }
+}
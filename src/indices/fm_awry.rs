@@ -1,3 +1,6 @@
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
 use awry::alphabet::SymbolAlphabet;
 use awry::fm_index::{FmBuildArgs, FmIndex};
 use itertools::Itertools;
@@ -6,36 +9,48 @@ use packed_seq::PackedSeq;
 use serde_json::Value;
 use tracing::{info, trace, warn};
 
-use crate::{Index, IndexBuilder};
+use crate::{Index, IndexBuilder, ToWriter};
+
+/// Identifies an `FmAwry` persisted file so loading a mismatched file fails
+/// cleanly instead of producing garbage.
+const MAGIC: &[u8; 8] = b"UIDXFA1\0";
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct FmAwryParams {
     pub sa_sampling: usize,
+    /// Directory `try_build_with_stats` writes its scratch FASTA file to.
+    /// Each build gets a freshly-named file here (removed once AWRY has
+    /// read it), instead of the fixed `/tmp/input.fa` of old, so builds
+    /// running concurrently (e.g. under the `par` flag elsewhere in this
+    /// module) don't race on the same path.
+    pub scratch_dir: PathBuf,
 }
 
 #[derive(MemSize)]
 pub struct FmAwry {
     fm: awry::fm_index::FmIndex,
     explode: bool,
+    /// A copy of the minimizer-space bytes `fm` was built from. AWRY's
+    /// `FmIndex` has no (de)serialization hook beyond building fresh from a
+    /// FASTA file, so `save`/`load` persist these and rebuild `fm` through
+    /// [`FmAwryParams::build_fm`] again, the same way it was built the first
+    /// time.
+    text: Vec<u8>,
 }
 
-impl IndexBuilder for FmAwryParams {
-    fn try_build_with_stats(
-        &self,
-        text: Vec<u8>,
-        width: usize,
-        stats: &crate::utils::Stats,
-    ) -> Option<Box<dyn Index>> {
+impl FmAwryParams {
+    /// Build an AWRY `FmIndex` from minimizer-space `text`, shared by
+    /// [`IndexBuilder::try_build_with_stats`] and [`FmAwry::load`] (which
+    /// reruns this on previously-[`FmAwry::save`]d bytes, since AWRY itself
+    /// offers no cheaper way to reload one).
+    fn build_fm(&self, text: &[u8]) -> Option<(awry::fm_index::FmIndex, bool)> {
         // AWRY does not support generic ASCII alphabet, so we 'explode' each byte into 4 DNA characters.
         let unpacked = PackedSeq {
-            seq: &text,
+            seq: text,
             offset: 0,
             len: 4 * text.len(),
         }
         .unpack();
-        stats.set_val("index", Value::String("FM-awry".to_string()));
-        stats.set("index_width", width);
-        stats.set("index_sa_sampling", self.sa_sampling as u64);
 
         let max = text.iter().copied().max().unwrap();
         trace!("Max value in text: {}", max);
@@ -52,7 +67,7 @@ impl IndexBuilder for FmAwryParams {
         } else {
             // AWRY does not support generic ASCII alphabet, so we 'explode' each byte into 4 DNA characters.
             let unpacked = PackedSeq {
-                seq: &text,
+                seq: text,
                 offset: 0,
                 len: 4 * text.len(),
             }
@@ -64,12 +79,17 @@ impl IndexBuilder for FmAwryParams {
         }
 
         fasta.push(b'\n');
-        let path = "/tmp/input.fa";
-        // Write text to input file.
-        std::fs::write(path, fasta).unwrap();
+        // A uniquely-named scratch file, so concurrent builds (e.g. under
+        // `par`) don't race on a shared path; removed once AWRY has read it.
+        let input_file = tempfile::Builder::new()
+            .prefix("uidx-fm-awry-")
+            .suffix(".fa")
+            .tempfile_in(&self.scratch_dir)
+            .expect("create scratch FASTA file for AWRY build");
+        std::fs::write(input_file.path(), fasta).unwrap();
 
         let build_args = FmBuildArgs {
-            input_file_src: path.into(),
+            input_file_src: input_file.path().into(),
             suffix_array_output_src: None,
             suffix_array_compression_ratio: Some(self.sa_sampling.try_into().unwrap()),
             lookup_table_kmer_len: None,
@@ -79,8 +99,38 @@ impl IndexBuilder for FmAwryParams {
         };
 
         let fm = FmIndex::new(&build_args).unwrap();
-        std::fs::remove_file(path).unwrap();
-        Some(Box::new(FmAwry { fm, explode }))
+        // `input_file` is removed here, once AWRY is done reading it.
+        drop(input_file);
+        Some((fm, explode))
+    }
+}
+
+impl IndexBuilder for FmAwryParams {
+    fn try_build_with_stats(
+        &self,
+        text: Vec<u8>,
+        width: usize,
+        stats: &crate::utils::Stats,
+    ) -> Option<Box<dyn Index>> {
+        stats.set_val("index", Value::String("FM-awry".to_string()));
+        stats.set("index_width", width);
+        stats.set("index_sa_sampling", self.sa_sampling as u64);
+
+        let (fm, explode) = self.build_fm(&text)?;
+        Some(Box::new(FmAwry { fm, explode, text }))
+    }
+
+    /// Load a previously-[`Index::save`]d `FmAwry` back from `path`, by
+    /// replaying [`Self::build_fm`] on the persisted minimizer-space bytes.
+    fn load(&self, path: &Path) -> io::Result<Box<dyn Index>> {
+        let text = FmAwry::read_text(path)?;
+        let (fm, explode) = self.build_fm(&text).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "FmAwry::load: persisted text failed to rebuild (unexpected byte values)",
+            )
+        })?;
+        Ok(Box::new(FmAwry { fm, explode, text }))
     }
 }
 
@@ -128,4 +178,76 @@ impl Index for FmAwry {
                 }),
         )
     }
+
+    /// AWRY's `count_string` walks the BWT without ever materializing
+    /// suffix-array positions, unlike [`Self::query`]'s `locate_string`. In
+    /// `explode` mode this counts every exploded match regardless of
+    /// alignment to the original 4-bases-per-byte boundary, so it can
+    /// overcount relative to `query(..).count()`; non-exploded DNA/packed
+    /// input (the common case) is exact.
+    fn count(&self, pattern: &[u8], _seq: PackedSeq, _sketcher: &dyn crate::Sketcher) -> usize {
+        let unpacked = unsafe {
+            String::from_utf8_unchecked(if !self.explode {
+                pattern
+                    .iter()
+                    .copied()
+                    .map(|x| packed_seq::unpack(x))
+                    .collect_vec()
+            } else {
+                PackedSeq {
+                    seq: &pattern,
+                    offset: 0,
+                    len: 4 * pattern.len(),
+                }
+                .unpack()
+            })
+        };
+
+        self.fm.count_string(&unpacked) as usize
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+        self.to_writer(&mut w)
+    }
+}
+
+impl ToWriter for FmAwry {
+    /// Write `text` (and the `explode` flag) behind a small header, so
+    /// [`FmAwry::read_text`] can read it back for [`FmAwryParams::load`].
+    fn to_writer(&self, w: &mut dyn Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[self.explode as u8])?;
+        w.write_all(&(self.text.len() as u64).to_le_bytes())?;
+        w.write_all(&self.text)?;
+        Ok(())
+    }
+}
+
+impl FmAwry {
+    /// Read the minimizer-space bytes previously written by
+    /// [`ToWriter::to_writer`]/[`Index::save`]. The `explode` flag written
+    /// alongside isn't needed here — [`FmAwryParams::build_fm`] recomputes
+    /// it from `text` itself — but is still read (and checked for presence)
+    /// so a truncated file is rejected up front rather than only once the
+    /// rebuilt index starts answering queries wrong.
+    fn read_text(path: &Path) -> io::Result<Vec<u8>> {
+        let mut r = io::BufReader::new(std::fs::File::open(path)?);
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not an FmAwry file (magic mismatch)",
+            ));
+        }
+        let mut explode = [0u8; 1];
+        r.read_exact(&mut explode)?;
+        let mut len_buf = [0u8; 8];
+        r.read_exact(&mut len_buf)?;
+        let text_len = u64::from_le_bytes(len_buf) as usize;
+        let mut text = vec![0u8; text_len];
+        r.read_exact(&mut text)?;
+        Ok(text)
+    }
 }
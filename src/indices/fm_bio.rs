@@ -112,3 +112,40 @@ impl Index for FmBio {
         Box::new(positions.into_iter())
     }
 }
+
+impl FmBio {
+    /// Find all maximal exact matches (MEMs) of `pattern` against the
+    /// indexed text, instead of only reporting full matches of the whole
+    /// pattern: repeatedly extend a backward search as far left as possible,
+    /// record the occurrences of the longest interval that is still
+    /// non-empty together with the matched length, then restart the search
+    /// from the character where the extension failed. Useful for
+    /// approximate/seed-and-extend alignment, where a partial hit is more
+    /// useful than an all-or-nothing answer.
+    pub fn query_mems<'p>(&'p self, pattern: &'p [u8]) -> Vec<(usize, usize)> {
+        let mut mems = Vec::new();
+        let mut end = pattern.len();
+        while end > 0 {
+            let mut start = end;
+            while start > 0
+                && matches!(
+                    self.fm.backward_search(pattern[start - 1..end].iter()),
+                    BackwardSearchResult::Complete(_)
+                )
+            {
+                start -= 1;
+            }
+            if start < end {
+                if let BackwardSearchResult::Complete(sai) =
+                    self.fm.backward_search(pattern[start..end].iter())
+                {
+                    let match_len = end - start;
+                    mems.extend(sai.occ(&self.sampled_sa).into_iter().map(|pos| (pos, match_len)));
+                }
+            }
+            // Restart the search from the character where the extension failed.
+            end = if start < end { start } else { end - 1 };
+        }
+        mems
+    }
+}
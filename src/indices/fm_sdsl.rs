@@ -1,12 +1,21 @@
-use std::{any::type_name, marker::PhantomData};
+use std::{
+    any::type_name,
+    io::{self, Read, Write},
+    marker::PhantomData,
+    path::Path,
+};
 
-use crate::{Index, IndexBuilder};
+use crate::{Index, IndexBuilder, ToWriter};
 use mem_dbg::MemSize;
 use packed_seq::SeqVec;
 use sdsl_lite_fm::*;
 use serde_json::Value;
 use tracing::{info, trace, warn};
 
+/// Identifies an `FmSdsl` persisted file so loading a mismatched file fails
+/// cleanly instead of producing garbage.
+const MAGIC: &[u8; 8] = b"UIDXFS1\0";
+
 pub struct FmSdslParams<T: SdslFmIndex<C>, C> {
     _c: PhantomData<C>,
     _t: PhantomData<T>,
@@ -40,12 +49,71 @@ impl<T: SdslFmIndex<C>, C> Copy for FmSdslParams<T, C> {}
 
 pub struct FmSdsl<T: SdslFmIndex<C>, C> {
     fm: T,
+    /// A copy of the minimizer-space bytes `fm` was built from.
+    /// `sdsl_lite_fm::SdslFmIndex` has no (de)serialization of its own to
+    /// hook into, so `save`/`load` persist these and rebuild `fm` via
+    /// `T::new` again, the same way [`FmSdslParams::try_build_with_stats`]
+    /// built it the first time.
+    text: Vec<u8>,
     _phantom_c: PhantomData<C>,
 }
 
 impl<T: SdslFmIndex<C>, C> MemSize for FmSdsl<T, C> {
     fn mem_size(&self, _flags: mem_dbg::SizeFlags) -> usize {
-        self.fm.size()
+        self.fm.size() + self.text.mem_size(mem_dbg::SizeFlags::default())
+    }
+}
+
+impl<T: SdslFmIndex<C>, C> ToWriter for FmSdsl<T, C> {
+    /// Write the raw bytes `fm` was built from behind a small header, so
+    /// [`FmSdsl::load`] can rebuild `fm` via `T::new`.
+    fn to_writer(&self, w: &mut dyn Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&(self.text.len() as u64).to_le_bytes())?;
+        w.write_all(&self.text)?;
+        Ok(())
+    }
+}
+
+impl<T: SdslFmIndex<C>, C> FmSdsl<T, C> {
+    /// Write `self` to `path` via [`ToWriter`].
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+        self.to_writer(&mut w)?;
+        w.flush()
+    }
+
+    /// Read an `FmSdsl` previously written by [`Self::save`]: read back the
+    /// minimizer-space bytes and re-run `T::new` on them via a scratch file,
+    /// exactly as [`FmSdslParams::try_build_with_stats`] did to build `fm`
+    /// the first time.
+    fn load(path: &Path, width: usize) -> io::Result<Self> {
+        let mut r = io::BufReader::new(std::fs::File::open(path)?);
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not an FmSdsl file (magic mismatch)",
+            ));
+        }
+        let mut len_buf = [0u8; 8];
+        r.read_exact(&mut len_buf)?;
+        let text_len = u64::from_le_bytes(len_buf) as usize;
+        let mut text = vec![0u8; text_len];
+        r.read_exact(&mut text)?;
+
+        let scratch = tempfile::Builder::new()
+            .prefix("uidx-fm-sdsl-")
+            .tempfile()?;
+        std::fs::write(scratch.path(), &text)?;
+        let fm = T::new(scratch.path().to_str().unwrap(), width);
+
+        Ok(Self {
+            fm,
+            text,
+            _phantom_c: PhantomData,
+        })
     }
 }
 
@@ -97,15 +165,22 @@ where
         }
 
         let path = "/tmp/input";
-        std::fs::write(path, text).unwrap();
+        std::fs::write(path, &text).unwrap();
         trace!("Written to /tmp/input");
         trace!("width: {}", width);
 
         Some(Box::new(FmSdsl::<T, C> {
             fm: T::new(path, width),
+            text,
             _phantom_c: PhantomData,
         }))
     }
+
+    /// Load a previously-[`Index::save`]d `FmSdsl` back from `path`, via
+    /// [`FmSdsl::load`].
+    fn load(&self, path: &Path, width: usize) -> io::Result<Box<dyn Index<SV>>> {
+        Ok(Box::new(FmSdsl::<T, C>::load(path, width)?))
+    }
 }
 
 impl<T: SdslFmIndex<u64>, SV: SeqVec + 'static> Index<SV> for FmSdsl<T, u64> {
@@ -133,6 +208,36 @@ impl<T: SdslFmIndex<u64>, SV: SeqVec + 'static> Index<SV> for FmSdsl<T, u64> {
         let len = positions.len();
         Box::new((0..len).map(move |i| positions.get(i) * width))
     }
+
+    fn query_into(
+        &self,
+        pattern: &[u8],
+        _seq: SV::Seq<'_>,
+        sketcher: &dyn crate::Sketcher<SV>,
+        out: &mut Vec<usize>,
+    ) -> usize {
+        let width = sketcher.width();
+        assert!(width <= 8);
+
+        let mut ints = vec![0u64; pattern.len() / width];
+        assert_eq!(ints.len() * width, pattern.len());
+        for i in 0..ints.len() {
+            let mut bytes = [0u8; 8];
+            bytes[8 - width..].copy_from_slice(&pattern[i * width..i * width + width]);
+            ints[i] = u64::from_be_bytes(bytes);
+            assert!(ints[i] < 1 << (width * 8));
+        }
+
+        // Already materializes the positions itself, so copy straight into `out`.
+        let positions = self.fm.locate(&ints);
+        let len = positions.len();
+        out.extend((0..len).map(|i| positions.get(i) * width));
+        len
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        FmSdsl::save(self, path)
+    }
 }
 
 impl<T: SdslFmIndex<u8>, SV: SeqVec> Index<SV> for FmSdsl<T, u8> {
@@ -146,4 +251,21 @@ impl<T: SdslFmIndex<u8>, SV: SeqVec> Index<SV> for FmSdsl<T, u8> {
         let len = positions.len();
         Box::new((0..len).map(move |i| positions.get(i)))
     }
+
+    fn query_into(
+        &self,
+        pattern: &[u8],
+        _seq: SV::Seq<'_>,
+        _sketcher: &dyn crate::Sketcher<SV>,
+        out: &mut Vec<usize>,
+    ) -> usize {
+        let positions = self.fm.locate(&pattern);
+        let len = positions.len();
+        out.extend((0..len).map(|i| positions.get(i)));
+        len
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        FmSdsl::save(self, path)
+    }
 }
@@ -35,9 +35,18 @@ impl<SV: SeqVec> IndexBuilder<SV> for DivSufSortSa {
             sa.retain(|x| *x % width as i32 == 0);
         }
 
-        Box::new(SuffixArray {
-            sa,
-            ms_seq: self.store_ms_seq.then(|| ms_seq),
-        })
+        Box::new(SuffixArray::new(sa, self.store_ms_seq.then(|| ms_seq)))
+    }
+
+    fn load(&self, path: &std::path::Path, _width: usize) -> std::io::Result<Box<dyn Index<SV>>> {
+        Ok(Box::new(SuffixArray::<i32>::load(path)?))
+    }
+
+    fn load_mmap(
+        &self,
+        path: &std::path::Path,
+        _width: usize,
+    ) -> std::io::Result<Box<dyn Index<SV>>> {
+        Ok(Box::new(SuffixArray::<i32>::load_mmap(path)?))
     }
 }
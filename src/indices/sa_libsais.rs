@@ -1,16 +1,16 @@
-use std::collections::HashMap;
-
 use packed_seq::SeqVec;
 use serde_json::Value;
 use tracing::trace;
 
-use super::suffix_array::SuffixArray;
+use super::suffix_array::{peek_sa_width, SuffixArray};
 use crate::{
     utils::{Stats, Timer},
     Index, IndexBuilder,
 };
 
-/// Build a 32-bit suffix array using `libsais`.
+/// Build a suffix array using `libsais`. For 3-or-more-byte minimizers,
+/// chooses 32- or 64-bit entries (see [`SuffixArray`]) based on the number
+/// of minimizer positions; 1- and 2-byte input always gets 32-bit entries.
 #[derive(Clone, Copy, Debug)]
 pub struct LibSaisSa {
     pub store_ms_seq: bool,
@@ -36,11 +36,10 @@ impl<SV: SeqVec> IndexBuilder<SV> for LibSaisSa {
 
         // If we do not store the ms_seq, first invert the byte order of each minimizer to make sorting aligned with sorting packed u64's.
 
-        let mut sa;
         match width {
             0 => panic!("Width 0 is not allowed"),
             1 => {
-                sa = vec![0; ms_seq.len()];
+                let mut sa = vec![0; ms_seq.len()];
                 if self.par {
                     #[cfg(feature = "openmp")]
                     libsais_rs::par::suffix_array_u8(&ms_seq, &mut sa).expect("suffix array");
@@ -49,177 +48,174 @@ impl<SV: SeqVec> IndexBuilder<SV> for LibSaisSa {
                 } else {
                     libsais_rs::suffix_array_u8(&ms_seq, &mut sa).expect("suffix array");
                 }
+                stats.set("index_sa_width", 4u64);
+                Box::new(SuffixArray::<i32>::new(sa, self.store_ms_seq.then(|| ms_seq)))
             }
             2 => {
                 trace!("Building 16-bit suffix array");
                 trace!("Transmuting..");
-                let (head, ms_seq, tail) = unsafe { ms_seq.as_mut_slice().align_to_mut::<u16>() };
+                let (head, ms_seq_u16, tail) =
+                    unsafe { ms_seq.as_mut_slice().align_to_mut::<u16>() };
                 assert!(head.is_empty());
                 assert!(tail.is_empty());
                 // TODO: Instead prefer to write in the right way directly during sketching.
                 trace!("Reversing byte order..");
-                for x in ms_seq.iter_mut() {
+                for x in ms_seq_u16.iter_mut() {
                     *x = x.swap_bytes();
                 }
 
-                sa = vec![0; ms_seq.len()];
+                let mut sa = vec![0; ms_seq_u16.len()];
                 trace!("Building suffix array");
                 if self.par {
                     #[cfg(feature = "openmp")]
-                    libsais_rs::par::suffix_array_u16(&ms_seq, &mut sa).expect("suffix array");
+                    libsais_rs::par::suffix_array_u16(&ms_seq_u16, &mut sa).expect("suffix array");
                     #[cfg(not(feature = "openmp"))]
                     panic!("Parallel suffix array construction with libsais required the 'openmp' feature.");
                 } else {
-                    libsais_rs::suffix_array_u16(&ms_seq, &mut sa).expect("suffix array");
+                    libsais_rs::suffix_array_u16(&ms_seq_u16, &mut sa).expect("suffix array");
                 }
                 trace!("Reversing byte order back..");
-                for x in ms_seq.iter_mut() {
+                for x in ms_seq_u16.iter_mut() {
                     *x = x.swap_bytes();
                 }
                 trace!("Suffix array built");
                 for x in sa.iter_mut() {
                     *x *= 2;
                 }
+                stats.set("index_sa_width", 4u64);
+                Box::new(SuffixArray::<i32>::new(sa, self.store_ms_seq.then(|| ms_seq)))
             }
             3.. => {
                 trace!("Building suffix array on {width}-byte input. First remapping to small i32 values.");
-                let minimizers_vals = ms_seq.chunks(width).map(|x| {
-                    let mut val = [0u8; 8];
-                    val[8 - width..].copy_from_slice(x);
-                    usize::from_be_bytes(val)
-                });
-
-                let mut vals_map = HashMap::new();
-                for x in minimizers_vals.clone() {
-                    vals_map.insert(x, 0i32);
-                }
-                let mut vals = vals_map.iter().map(|x| *x.0).collect::<Vec<_>>();
-                vals.sort_unstable();
-                trace!("MIN VAL {:?}", vals.iter().min().unwrap());
-                trace!("MAX VAL {:?}", vals.iter().max().unwrap());
-                for (i, x) in vals.iter().enumerate() {
-                    *vals_map.get_mut(x).unwrap() = i as i32;
+
+                // The alphabet (number of distinct minimizers) always fits
+                // an i32, but the number of *positions* doesn't once the
+                // text holds more than `i32::MAX` minimizers: build a
+                // 64-bit suffix array in that case instead of silently
+                // overflowing a `Vec<i32>`.
+                let num_positions = ms_seq.len() / width;
+
+                // Rank each minimizer value by sorting `(value, original
+                // index)` pairs once, instead of a `HashMap` insert-then-probe
+                // per position: no hashing, and the sort is cache-friendly and
+                // trivially parallelizable (e.g. under `self.par`) for the
+                // hundreds-of-millions-of-minimizers case.
+                let mut keyed: Vec<(u64, u32)> = ms_seq
+                    .chunks(width)
+                    .enumerate()
+                    .map(|(i, x)| {
+                        let mut val = [0u8; 8];
+                        val[8 - width..].copy_from_slice(x);
+                        (u64::from_be_bytes(val), i as u32)
+                    })
+                    .collect();
+                keyed.sort_unstable();
+
+                let mut ranks = vec![0i32; num_positions];
+                let mut alphabet_size = 0i32;
+                let mut prev_val = None;
+                for (val, idx) in &keyed {
+                    if prev_val != Some(*val) {
+                        trace!("new distinct minimizer value {val}");
+                        alphabet_size += 1;
+                        prev_val = Some(*val);
+                    }
+                    ranks[*idx as usize] = alphabet_size - 1;
                 }
-                let alphabet_size = vals.len() + 1;
-                drop(vals);
+                drop(keyed);
+                let alphabet_size = alphabet_size as usize + 1;
                 trace!("alphabet size {alphabet_size}");
                 assert!(alphabet_size < i32::MAX as usize);
-                let mut remapped_minimizer_vals: Vec<i32> = minimizers_vals
-                    .map(|x| *vals_map.get(&x).unwrap())
-                    .collect();
-                drop(vals_map);
-                trace!("Building suffix array");
-                sa = vec![0; ms_seq.len() / width];
-                if self.par {
-                    #[cfg(feature = "openmp")]
-                    libsais_rs::par::suffix_array_i32(
-                        &mut remapped_minimizer_vals,
-                        &mut sa,
-                        alphabet_size,
-                    )
-                    .expect("suffix array");
-                    #[cfg(not(feature = "openmp"))]
-                    panic!("Parallel suffix array construction with libsais required the 'openmp' feature.");
+
+                if num_positions > i32::MAX as usize {
+                    trace!("{num_positions} positions exceed i32::MAX; building a 64-bit suffix array");
+                    stats.set("index_sa_width", 8u64);
+                    let mut remapped_minimizer_vals: Vec<i64> =
+                        ranks.iter().map(|&x| x as i64).collect();
+                    drop(ranks);
+                    trace!("Building suffix array");
+                    let mut sa = vec![0i64; num_positions];
+                    if self.par {
+                        #[cfg(feature = "openmp")]
+                        libsais_rs::par::long_suffix_array_i64(
+                            &mut remapped_minimizer_vals,
+                            &mut sa,
+                            alphabet_size as i64,
+                        )
+                        .expect("suffix array");
+                        #[cfg(not(feature = "openmp"))]
+                        panic!("Parallel suffix array construction with libsais required the 'openmp' feature.");
+                    } else {
+                        libsais_rs::long_suffix_array_i64(
+                            &mut remapped_minimizer_vals,
+                            &mut sa,
+                            alphabet_size as i64,
+                        )
+                        .expect("suffix array");
+                    }
+
+                    trace!("Suffix array built");
+                    for x in sa.iter_mut() {
+                        *x *= width as i64;
+                    }
+                    Box::new(SuffixArray::<i64>::new(sa, self.store_ms_seq.then(|| ms_seq)))
                 } else {
-                    libsais_rs::suffix_array_i32(
-                        &mut remapped_minimizer_vals,
-                        &mut sa,
-                        alphabet_size as i32,
-                    )
-                    .expect("suffix array");
-                }
+                    let mut remapped_minimizer_vals = ranks;
+                    trace!("Building suffix array");
+                    stats.set("index_sa_width", 4u64);
+                    let mut sa = vec![0i32; num_positions];
+                    if self.par {
+                        #[cfg(feature = "openmp")]
+                        libsais_rs::par::suffix_array_i32(
+                            &mut remapped_minimizer_vals,
+                            &mut sa,
+                            alphabet_size,
+                        )
+                        .expect("suffix array");
+                        #[cfg(not(feature = "openmp"))]
+                        panic!("Parallel suffix array construction with libsais required the 'openmp' feature.");
+                    } else {
+                        libsais_rs::suffix_array_i32(
+                            &mut remapped_minimizer_vals,
+                            &mut sa,
+                            alphabet_size as i32,
+                        )
+                        .expect("suffix array");
+                    }
 
-                trace!("Suffix array built");
-                for x in sa.iter_mut() {
-                    *x *= width as i32;
+                    trace!("Suffix array built");
+                    for x in sa.iter_mut() {
+                        *x *= width as i32;
+                    }
+                    Box::new(SuffixArray::<i32>::new(sa, self.store_ms_seq.then(|| ms_seq)))
                 }
-            } // 4 => {
-              //     trace!("Building 32-bit suffix array");
-              //     trace!("Transmuting..");
-              //     let (head, ms_seq, tail) = unsafe { ms_seq.as_mut_slice().align_to_mut::<i32>() };
-              //     assert!(head.is_empty(), "Head has size {}", head.len());
-              //     assert!(tail.is_empty(), "Tail has size {}", tail.len());
-              //     sa = vec![0; ms_seq.len()];
-              //     trace!("Reversing byte order..");
-              //     // FIXME: What exactly does alphabet size mean?
-              //     let alphabet_size = i32::MAX;
-              //     for x in ms_seq.iter_mut() {
-              //         *x = x.swap_bytes();
-              //     }
-              //     trace!("Building suffix array");
-              //     if self.par {
-              //         #[cfg(feature = "openmp")]
-              //         libsais_rs::par::suffix_array_i32(ms_seq, &mut sa, alphabet_size)
-              //             .expect("suffix array");
-              //         #[cfg(not(feature = "openmp"))]
-              //         panic!("Parallel suffix array construction with libsais required the 'openmp' feature.");
-              //     } else {
-              //         libsais_rs::suffix_array_i32(ms_seq, &mut sa, alphabet_size)
-              //             .expect("suffix array");
-              //     }
-              //     trace!("Reversing byte order back..");
-              //     for x in ms_seq.iter_mut() {
-              //         *x = x.swap_bytes();
-              //     }
-              //     trace!("Suffix array built");
-              //     for x in sa.iter_mut() {
-              //         *x *= 4;
-              //     }
-              // }
-              // 8 => {
-              //     trace!("Building 64-bit suffix array");
-              //     trace!("Transmuting..");
-              //     let (head, ms_seq, tail) = unsafe { ms_seq.as_mut_slice().align_to_mut::<i64>() };
-              //     assert!(head.is_empty(), "Head has size {}", head.len());
-              //     assert!(tail.is_empty(), "Tail has size {}", tail.len());
-              //     let mut sa_64 = vec![0; ms_seq.len()];
-              //     trace!("Reversing byte order..");
-              //     for x in ms_seq.iter_mut() {
-              //         *x = x.swap_bytes();
-              //     }
-              //     // FIXME: What exactly does alphabet size mean?
-              //     let alphabet_size = i64::MAX;
-              //     trace!("Building suffix array");
-              //     if self.par {
-              //         #[cfg(feature = "openmp")]
-              //         libsais_rs::par::long_suffix_array_i64(ms_seq, &mut sa_64, alphabet_size)
-              //             .expect("suffix array");
-              //         #[cfg(not(feature = "openmp"))]
-              //         panic!("Parallel suffix array construction with libsais required the 'openmp' feature.");
-              //     } else {
-              //         libsais_rs::long_suffix_array_i64(ms_seq, &mut sa_64, alphabet_size)
-              //             .expect("suffix array");
-              //     }
-              //     trace!("Reversing byte order back..");
-              //     for x in ms_seq.iter_mut() {
-              //         *x = x.swap_bytes();
-              //     }
-              //     trace!("Copy to 32-bit SA");
-              //     sa = sa_64.iter().map(|x| *x as i32).collect();
-              //     trace!("Suffix array built");
-              //     for x in sa_64.iter_mut() {
-              //         *x *= 8;
-              //     }
-              // }
-              // _ => {
-              //     // TODO: For remaining alphabet sizes, it's probably better to re-code them to the next power of 2 size.
-              //     sa = vec![0; ms_seq.len()];
-              //     if self.par {
-              //         #[cfg(feature = "openmp")]
-              //         libsais_rs::par::suffix_array_u8(&ms_seq, &mut sa).expect("suffix array");
-              //         #[cfg(not(feature = "openmp"))]
-              //         panic!("Parallel suffix array construction with libsais required the 'openmp' feature.");
-              //     } else {
-              //         libsais_rs::suffix_array_u8(&ms_seq, &mut sa).expect("suffix array");
-              //     }
-              //     timer.next("Compress SA");
-              //     sa.retain(|x| *x % width as i32 == 0);
-              // }
+            }
         }
+    }
+
+    fn load(&self, path: &std::path::Path, _width: usize) -> std::io::Result<Box<dyn Index<SV>>> {
+        match peek_sa_width(path)? {
+            4 => Ok(Box::new(SuffixArray::<i32>::load(path)?)),
+            8 => Ok(Box::new(SuffixArray::<i64>::load(path)?)),
+            tag => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Unknown SuffixArray entry-width tag {tag}"),
+            )),
+        }
+    }
 
-        Box::new(SuffixArray {
-            sa,
-            ms_seq: self.store_ms_seq.then(|| ms_seq),
-        })
+    fn load_mmap(
+        &self,
+        path: &std::path::Path,
+        _width: usize,
+    ) -> std::io::Result<Box<dyn Index<SV>>> {
+        match peek_sa_width(path)? {
+            4 => Ok(Box::new(SuffixArray::<i32>::load_mmap(path)?)),
+            8 => Ok(Box::new(SuffixArray::<i64>::load_mmap(path)?)),
+            tag => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Unknown SuffixArray entry-width tag {tag}"),
+            )),
+        }
     }
 }
@@ -1,40 +1,364 @@
-use std::cmp::Ordering;
+use std::{
+    cmp::Ordering,
+    io::{self, Read, Write},
+    path::Path,
+};
 
-use mem_dbg::{MemDbg, MemSize, SizeFlags};
-use packed_seq::Seq;
+use mem_dbg::{MemSize, SizeFlags};
+use packed_seq::SeqVec;
 
-use crate::{Index, Sketcher};
+use crate::{FromReader, Index, Sketcher, ToWriter};
 
-/// A 32-bit suffix array that owns the corresponding text.
-/// Uses `libdivsufsort` for searching.
-#[derive(MemSize, MemDbg)]
-pub struct SuffixArray {
-    pub(super) ms_seq: Option<Vec<u8>>,
-    pub(super) sa: Vec<i32>,
+/// Identifies a `SuffixArray` file so that loading a mismatched or
+/// unrelated file fails cleanly instead of producing garbage.
+const MAGIC: &[u8; 8] = b"UIDXSA1\0";
+
+/// Byte length of the fixed-size header written before `sa`'s entries:
+/// MAGIC(8) + TAG(1) + has_ms_seq(1) + sa_len(8) + ms_seq_len(8).
+const HEADER_LEN: usize = 8 + 1 + 1 + 8 + 8;
+
+/// Padding bytes needed after [`HEADER_LEN`] so that `sa`'s entries start at
+/// a `Sa::BYTES`-aligned file offset (entries are read back via
+/// [`SuffixArray::load_mmap`]'s `align_to`, which requires this).
+fn sa_pad<Sa: SaInt>() -> usize {
+    (Sa::BYTES - HEADER_LEN % Sa::BYTES) % Sa::BYTES
+}
+
+/// Integer type backing a [`SuffixArray`]'s entries and search indices.
+/// `i32` suffices for texts with fewer than `i32::MAX` minimizer positions;
+/// beyond that, `i64` is required so positions and search bounds don't
+/// overflow. Implemented only for those two types.
+pub trait SaInt:
+    Copy + Ord + std::fmt::Debug + MemSize + Send + Sync + std::ops::Add<Output = Self> + std::ops::Sub<Output = Self> + 'static
+{
+    /// Tag byte stored in the file header so loading picks the matching width.
+    const TAG: u8;
+    const BYTES: usize;
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn from_usize(x: usize) -> Self;
+    fn to_usize(self) -> usize;
+    fn to_le_bytes(self) -> Vec<u8>;
+    fn from_le_bytes(buf: &[u8]) -> Self;
+    /// `self / 2`.
+    fn half(self) -> Self;
+    /// `self & 1 != 0`.
+    fn is_odd(self) -> bool;
 }
 
-impl SuffixArray {
+impl SaInt for i32 {
+    const TAG: u8 = 4;
+    const BYTES: usize = 4;
+    const ZERO: Self = 0;
+    const ONE: Self = 1;
+
+    fn from_usize(x: usize) -> Self {
+        x as i32
+    }
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+    fn to_le_bytes(self) -> Vec<u8> {
+        i32::to_le_bytes(self).to_vec()
+    }
+    fn from_le_bytes(buf: &[u8]) -> Self {
+        i32::from_le_bytes(buf.try_into().unwrap())
+    }
+    fn half(self) -> Self {
+        self / 2
+    }
+    fn is_odd(self) -> bool {
+        self & 1 != 0
+    }
+}
+
+impl SaInt for i64 {
+    const TAG: u8 = 8;
+    const BYTES: usize = 8;
+    const ZERO: Self = 0;
+    const ONE: Self = 1;
+
+    fn from_usize(x: usize) -> Self {
+        x as i64
+    }
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+    fn to_le_bytes(self) -> Vec<u8> {
+        i64::to_le_bytes(self).to_vec()
+    }
+    fn from_le_bytes(buf: &[u8]) -> Self {
+        i64::from_le_bytes(buf.try_into().unwrap())
+    }
+    fn half(self) -> Self {
+        self / 2
+    }
+    fn is_odd(self) -> bool {
+        self & 1 != 0
+    }
+}
+
+/// Read just the on-disk integer-width tag (written alongside the rest of
+/// the header by [`ToWriter::to_writer`]), without assuming a width, so a
+/// builder can pick [`SuffixArray<i32>`] vs [`SuffixArray<i64>`] before
+/// fully loading.
+pub fn peek_sa_width(path: &Path) -> io::Result<u8> {
+    let mut r = io::BufReader::new(std::fs::File::open(path)?);
+    let mut magic = [0u8; 8];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Not a SuffixArray file (magic mismatch)",
+        ));
+    }
+    let mut tag = [0u8; 1];
+    r.read_exact(&mut tag)?;
+    Ok(tag[0])
+}
+
+/// Backing storage for [`SuffixArray::sa`]: either a heap-allocated `Vec`
+/// (freshly built, or read via [`SuffixArray::load`]), or a slice borrowed
+/// from the mapping created by [`SuffixArray::load_mmap`] (kept alive
+/// alongside it in `SuffixArray::_mmap`), so the hot `sa_search` path reads
+/// straight from mapped pages instead of a heap copy.
+enum SaStorage<Sa: SaInt> {
+    Owned(Vec<Sa>),
+    Mapped(&'static [Sa]),
+}
+
+impl<Sa: SaInt> SaStorage<Sa> {
+    fn as_slice(&self) -> &[Sa] {
+        match self {
+            SaStorage::Owned(v) => v,
+            SaStorage::Mapped(s) => s,
+        }
+    }
+}
+
+/// Backing storage for [`SuffixArray::ms_seq`]; mirrors [`SaStorage`].
+enum MsSeqStorage {
+    Owned(Vec<u8>),
+    Mapped(&'static [u8]),
+}
+
+impl MsSeqStorage {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            MsSeqStorage::Owned(v) => v,
+            MsSeqStorage::Mapped(s) => s,
+        }
+    }
+}
+
+/// A suffix array that owns the corresponding text, backed by `Sa`-wide
+/// entries (`i32` or `i64`, see [`SaInt`]). Uses `libdivsufsort`/`libsais`
+/// for construction and a transcribed `libdivsufsort` binary search for
+/// querying.
+pub struct SuffixArray<Sa: SaInt = i32> {
+    pub(super) ms_seq: Option<MsSeqStorage>,
+    pub(super) sa: SaStorage<Sa>,
+    /// The mapping `sa`/`ms_seq` borrow from when loaded via
+    /// [`Self::load_mmap`]; `None` otherwise. Never read directly — it
+    /// exists only to keep the mapping alive as long as `self` does.
+    _mmap: Option<memmap2::Mmap>,
+}
+
+impl<Sa: SaInt> MemSize for SuffixArray<Sa> {
+    /// An `mmap`-backed `sa`/`ms_seq` shares read-only pages with the file
+    /// cache instead of allocating, so (unlike an owned `Vec`) it doesn't
+    /// count towards heap usage here.
+    fn mem_size(&self, flags: SizeFlags) -> usize {
+        let sa_size = match &self.sa {
+            SaStorage::Owned(v) => v.mem_size(flags),
+            SaStorage::Mapped(_) => 0,
+        };
+        let ms_seq_size = match &self.ms_seq {
+            Some(MsSeqStorage::Owned(v)) => v.mem_size(flags),
+            Some(MsSeqStorage::Mapped(_)) | None => 0,
+        };
+        sa_size + ms_seq_size
+    }
+}
+
+impl<Sa: SaInt> ToWriter for SuffixArray<Sa> {
+    /// Write `sa` (and `ms_seq`, if stored) behind a small header (magic,
+    /// entry-width tag, whether `ms_seq` is present, and both lengths) so
+    /// that [`FromReader::from_reader`]/[`SuffixArray::load_mmap`] can
+    /// validate and reconstruct it.
+    fn to_writer(&self, w: &mut dyn Write) -> io::Result<()> {
+        let sa = self.sa.as_slice();
+        let ms_seq = self.ms_seq.as_ref().map(MsSeqStorage::as_slice);
+        w.write_all(MAGIC)?;
+        w.write_all(&[Sa::TAG])?;
+        w.write_all(&[ms_seq.is_some() as u8])?;
+        w.write_all(&(sa.len() as u64).to_le_bytes())?;
+        w.write_all(&(ms_seq.map_or(0, |s| s.len()) as u64).to_le_bytes())?;
+        w.write_all(&vec![0u8; sa_pad::<Sa>()])?;
+        for x in sa {
+            w.write_all(&x.to_le_bytes())?;
+        }
+        if let Some(ms_seq) = ms_seq {
+            w.write_all(ms_seq)?;
+        }
+        Ok(())
+    }
+}
+
+impl<Sa: SaInt> FromReader for SuffixArray<Sa> {
+    /// Read a `SuffixArray` previously written by [`ToWriter::to_writer`],
+    /// copying `sa` and `ms_seq` into freshly allocated `Vec`s.
+    fn from_reader(r: &mut dyn Read) -> io::Result<Self> {
+        let (has_ms_seq, sa_len, ms_seq_len) = Self::read_header(r)?;
+        let mut pad = vec![0u8; sa_pad::<Sa>()];
+        r.read_exact(&mut pad)?;
+
+        let mut sa = Vec::with_capacity(sa_len);
+        let mut buf = vec![0u8; Sa::BYTES];
+        for _ in 0..sa_len {
+            r.read_exact(&mut buf)?;
+            sa.push(Sa::from_le_bytes(&buf));
+        }
+        let ms_seq = has_ms_seq.then(|| {
+            let mut ms_seq = vec![0u8; ms_seq_len];
+            r.read_exact(&mut ms_seq).map(|_| ms_seq)
+        });
+        let ms_seq = ms_seq.transpose()?;
+
+        Ok(Self::new(sa, ms_seq))
+    }
+}
+
+impl<Sa: SaInt> SuffixArray<Sa> {
+    /// Build a `SuffixArray` owning freshly-built `sa`/`ms_seq` data.
+    pub(super) fn new(sa: Vec<Sa>, ms_seq: Option<Vec<u8>>) -> Self {
+        Self {
+            sa: SaStorage::Owned(sa),
+            ms_seq: ms_seq.map(MsSeqStorage::Owned),
+            _mmap: None,
+        }
+    }
+
+    /// Write `self` to `path` via [`ToWriter`].
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+        self.to_writer(&mut w)?;
+        w.flush()
+    }
+
+    /// Read a `SuffixArray` previously written by [`Self::save`] via [`FromReader`].
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut r = io::BufReader::new(std::fs::File::open(path)?);
+        Self::from_reader(&mut r)
+    }
+
+    /// Like [`Self::load`], but memory-maps the file read-only and borrows
+    /// `sa` (and `ms_seq`, if present) directly from the mapping instead of
+    /// copying it into the heap, so a multi-gigabyte index can be reloaded
+    /// — and, since the mapping is read-only, shared across processes —
+    /// without ever materializing it as a fresh allocation.
+    pub fn load_mmap(path: &Path) -> io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        let mut cursor = io::Cursor::new(&mmap[..]);
+        let (has_ms_seq, sa_len, ms_seq_len) = Self::read_header(&mut cursor)?;
+        let data_start = cursor.position() as usize + sa_pad::<Sa>();
+
+        let sa_bytes = &mmap[data_start..data_start + sa_len * Sa::BYTES];
+        // SAFETY: `sa_bytes` is `sa_len` little-endian `Sa`s written by
+        // `save`, padded by `sa_pad` so this offset is `Sa::BYTES`-aligned.
+        // We don't just trust that, though: `align_to` itself tells us
+        // whether the slice it was handed is actually aligned, so we assert
+        // its `prefix` is empty and its `middle` covers all `sa_len`
+        // entries before relying on it. The slice borrows from `mmap`,
+        // which outlives it as `self._mmap` for as long as `self` (and thus
+        // this `SaStorage::Mapped`) is alive.
+        let sa: &'static [Sa] = unsafe {
+            let (prefix, aligned, _suffix) = sa_bytes.align_to::<Sa>();
+            assert!(
+                prefix.is_empty(),
+                "SuffixArray::load_mmap: sa region is not Sa-aligned"
+            );
+            assert_eq!(
+                aligned.len(),
+                sa_len,
+                "SuffixArray::load_mmap: sa region length mismatch"
+            );
+            std::slice::from_raw_parts(aligned.as_ptr(), aligned.len())
+        };
+        let ms_seq = has_ms_seq.then(|| {
+            let start = data_start + sa_len * Sa::BYTES;
+            let bytes = &mmap[start..start + ms_seq_len];
+            // SAFETY: same reasoning as `sa` above.
+            unsafe { std::slice::from_raw_parts(bytes.as_ptr(), bytes.len()) }
+        });
+
+        Ok(Self {
+            sa: SaStorage::Mapped(sa),
+            ms_seq: ms_seq.map(MsSeqStorage::Mapped),
+            _mmap: Some(mmap),
+        })
+    }
+
+    fn read_header(r: &mut impl Read) -> io::Result<(bool, usize, usize)> {
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a SuffixArray file (magic mismatch)",
+            ));
+        }
+        let mut tag = [0u8; 1];
+        r.read_exact(&mut tag)?;
+        if tag[0] != Sa::TAG {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "SuffixArray entry-width mismatch: file uses {}-byte entries, expected {}",
+                    tag[0],
+                    Sa::BYTES
+                ),
+            ));
+        }
+        let mut has_ms_seq = [0u8; 1];
+        r.read_exact(&mut has_ms_seq)?;
+        let mut len_buf = [0u8; 8];
+        r.read_exact(&mut len_buf)?;
+        let sa_len = u64::from_le_bytes(len_buf) as usize;
+        r.read_exact(&mut len_buf)?;
+        let ms_seq_len = u64::from_le_bytes(len_buf) as usize;
+        Ok((has_ms_seq[0] != 0, sa_len, ms_seq_len))
+    }
+
     pub fn seq_size(&self) -> usize {
-        self.ms_seq.mem_size(SizeFlags::default())
+        match &self.ms_seq {
+            Some(MsSeqStorage::Owned(v)) => v.mem_size(SizeFlags::default()),
+            Some(MsSeqStorage::Mapped(_)) | None => 0,
+        }
     }
     pub fn sa_size(&self) -> usize {
-        self.sa.mem_size(SizeFlags::default())
+        match &self.sa {
+            SaStorage::Owned(v) => v.mem_size(SizeFlags::default()),
+            SaStorage::Mapped(_) => 0,
+        }
     }
 
     #[inline(always)]
-    fn compare_minimizers<'i>(
+    fn compare_minimizers<'i, SV: SeqVec>(
         &self,
-        seq: impl Seq<'i>,
+        seq: SV::Seq<'i>,
         pattern: &[u8],
         // Byte index in ms_seq.
         i: usize,
         // Byte index in pattern.
         j: usize,
-        sketcher: &impl Sketcher,
+        sketcher: &dyn Sketcher<SV>,
     ) -> Ordering {
         let w = sketcher.width() as usize;
         if let Some(ms_seq) = &self.ms_seq {
-            let t = &ms_seq[i..i + w];
+            let t = &ms_seq.as_slice()[i..i + w];
             let p = &pattern[j..j + w];
             t.cmp(p)
         } else {
@@ -44,44 +368,45 @@ impl SuffixArray {
         }
     }
 
-    fn compare<'i>(
+    fn compare<'i, SV: SeqVec>(
         &self,
-        sketcher: &impl Sketcher,
-        seq: impl Seq<'i>,
+        sketcher: &dyn Sketcher<SV>,
+        seq: SV::Seq<'i>,
         p: &[u8],
         // Byte-position in the sketched text that we compare against.
         // Must be a multiple of the kmer width.
-        suf: i32,
+        suf: Sa,
         // Number of bytes of pattern already matched.
         // Must be a multiple of the kmer width when matching full minimizers at a time.
-        match_: &mut i32,
+        match_: &mut Sa,
     ) -> Ordering {
-        let w = sketcher.width() as i32;
+        let width = sketcher.width();
+        let w = Sa::from_usize(width);
 
-        debug_assert_eq!(suf % w, 0);
-        debug_assert!(p.len() as i32 % w == 0);
+        debug_assert_eq!(suf.to_usize() % width, 0);
+        debug_assert!(p.len() % width == 0);
         if let Some(ms_seq) = &self.ms_seq {
-            debug_assert!(ms_seq.len() as i32 % w == 0);
+            debug_assert!(ms_seq.as_slice().len() % width == 0);
         }
 
         let mut i = suf + *match_;
         let mut j = *match_;
         let mut r = Ordering::Equal;
 
-        let ms_seq_len = sketcher.len() as i32 * w;
-        let pattern_len = p.len() as i32;
+        let ms_seq_len = Sa::from_usize(sketcher.len() * width);
+        let pattern_len = Sa::from_usize(p.len());
 
-        while i < ms_seq_len as i32 && j < pattern_len {
-            r = self.compare_minimizers(seq, p, i as usize, j as usize, sketcher);
+        while i < ms_seq_len && j < pattern_len {
+            r = self.compare_minimizers(seq, p, i.to_usize(), j.to_usize(), sketcher);
             if r != Ordering::Equal {
                 break;
             }
-            i += w;
-            j += w;
+            i = i + w;
+            j = j + w;
         }
         *match_ = j;
         if r.is_eq() {
-            if j != p.len() as i32 {
+            if j != pattern_len {
                 r = Ordering::Less;
             }
         }
@@ -92,66 +417,60 @@ impl SuffixArray {
     // https://github.com/y-256/libdivsufsort/blob/5f60d6f026c30fb4ac296f696b3c8b0eb71bd428/lib/utils.c
     /// Search text `t` for pattern `p` given (sparse) suffix array `sa`.
     /// Returns a `(pos, cnt)` pair where `pos` is the index of the first match and `cnt` is the number of matches.
-    fn sa_search<'i>(&self, sketcher: &impl Sketcher, seq: impl Seq<'i>, p: &[u8]) -> (i32, i32) {
-        let mut size = self.sa.len() as i32;
+    fn sa_search<'i, SV: SeqVec>(
+        &self,
+        sketcher: &dyn Sketcher<SV>,
+        seq: SV::Seq<'i>,
+        p: &[u8],
+    ) -> (Sa, Sa) {
+        let sa = self.sa.as_slice();
+        let mut size = Sa::from_usize(sa.len());
         let mut half;
         let mut match_;
-        let mut lmatch = 0;
-        let mut rmatch = 0;
+        let mut lmatch = Sa::ZERO;
+        let mut rmatch = Sa::ZERO;
         let mut llmatch;
         let mut lrmatch;
         let mut rlmatch;
         let mut rrmatch;
-        let mut i = 0;
-        let mut j = 0;
-        let mut k = 0;
+        let mut i = Sa::ZERO;
+        let mut j = Sa::ZERO;
+        let mut k = Sa::ZERO;
         let mut r;
 
-        if sketcher.len() == 0 || self.sa.is_empty() {
-            return (0, 0);
+        if sketcher.len() == 0 || sa.is_empty() {
+            return (Sa::ZERO, Sa::ZERO);
         }
         if p.is_empty() {
-            return (0, self.sa.len() as i32);
+            return (Sa::ZERO, Sa::from_usize(sa.len()));
         }
 
-        while size > 0 {
-            half = size / 2;
+        while size > Sa::ZERO {
+            half = size.half();
             match_ = lmatch.min(rmatch);
-            r = self.compare(
-                sketcher,
-                seq,
-                p,
-                self.sa[i as usize + half as usize],
-                &mut match_,
-            );
+            r = self.compare(sketcher, seq, p, sa[(i + half).to_usize()], &mut match_);
             if r.is_lt() {
-                i += half + 1;
-                half -= (size & 1) ^ 1;
+                i = i + half + Sa::ONE;
+                half = if size.is_odd() { half } else { half - Sa::ONE };
                 lmatch = match_;
             } else if r.is_gt() {
                 rmatch = match_;
             } else {
                 let mut lsize = half;
                 j = i;
-                let mut rsize = size - half - 1;
-                k = i + half + 1;
+                let mut rsize = size - half - Sa::ONE;
+                k = i + half + Sa::ONE;
 
                 /* left part */
                 llmatch = lmatch;
                 lrmatch = match_;
-                while lsize > 0 {
-                    half = lsize >> 1;
+                while lsize > Sa::ZERO {
+                    half = lsize.half();
                     lmatch = llmatch.min(lrmatch);
-                    r = self.compare(
-                        sketcher,
-                        seq,
-                        p,
-                        self.sa[j as usize + half as usize],
-                        &mut lmatch,
-                    );
+                    r = self.compare(sketcher, seq, p, sa[(j + half).to_usize()], &mut lmatch);
                     if r.is_lt() {
-                        j += half + 1;
-                        half -= (lsize & 1) ^ 1;
+                        j = j + half + Sa::ONE;
+                        half = if lsize.is_odd() { half } else { half - Sa::ONE };
                         llmatch = lmatch;
                     } else {
                         lrmatch = lmatch;
@@ -162,19 +481,13 @@ impl SuffixArray {
                 /* right part */
                 rlmatch = match_;
                 rrmatch = rmatch;
-                while rsize > 0 {
-                    half = rsize >> 1;
+                while rsize > Sa::ZERO {
+                    half = rsize.half();
                     rmatch = rlmatch.min(rrmatch);
-                    r = self.compare(
-                        sketcher,
-                        seq,
-                        p,
-                        self.sa[k as usize + half as usize],
-                        &mut rmatch,
-                    );
+                    r = self.compare(sketcher, seq, p, sa[(k + half).to_usize()], &mut rmatch);
                     if r.is_le() {
-                        k += half + 1;
-                        half -= (rsize & 1) ^ 1;
+                        k = k + half + Sa::ONE;
+                        half = if rsize.is_odd() { half } else { half - Sa::ONE };
                         rlmatch = rmatch;
                     } else {
                         rrmatch = rmatch;
@@ -186,19 +499,293 @@ impl SuffixArray {
             size = half;
         }
 
-        let idx = if k - j > 0 { j } else { i };
+        let idx = if k - j > Sa::ZERO { j } else { i };
         (idx, k - j)
     }
+
+    /// Compare the `j`-th minimizer of the suffix array entry `suf` against
+    /// `pattern`'s `j`-th minimizer (at `pattern[j*width..]`). A suffix shorter than `j+1`
+    /// minimizers sorts before any that has one, matching the usual
+    /// suffix-array convention that a prefix sorts before its extensions.
+    fn nth_minimizer_vs_pattern<SV: SeqVec>(
+        &self,
+        seq: SV::Seq<'_>,
+        pattern: &[u8],
+        suf: Sa,
+        j: usize,
+        sketcher: &dyn Sketcher<SV>,
+    ) -> Ordering {
+        let width = sketcher.width();
+        let ms_seq_len = sketcher.len() * width;
+        let i = suf.to_usize() + j * width;
+        if i + width > ms_seq_len {
+            return Ordering::Less;
+        }
+        self.compare_minimizers(seq, pattern, i, j * width, sketcher)
+    }
+
+    /// Like [`Self::nth_minimizer_vs_pattern`], but compares the `j`-th
+    /// minimizer of two suffix array entries against each other, for
+    /// [`Self::query_approx`]'s neighbour lookup.
+    fn nth_minimizer_vs_suffix<SV: SeqVec>(
+        &self,
+        seq: SV::Seq<'_>,
+        a: Sa,
+        b: Sa,
+        j: usize,
+        sketcher: &dyn Sketcher<SV>,
+    ) -> Ordering {
+        let width = sketcher.width();
+        let ms_seq_len = sketcher.len() * width;
+        let ia = a.to_usize() + j * width;
+        let ib = b.to_usize() + j * width;
+        let has_a = ia + width <= ms_seq_len;
+        let has_b = ib + width <= ms_seq_len;
+        match (has_a, has_b) {
+            (false, false) => Ordering::Equal,
+            (false, true) => Ordering::Less,
+            (true, false) => Ordering::Greater,
+            (true, true) => {
+                if let Some(ms_seq) = &self.ms_seq {
+                    let ms = ms_seq.as_slice();
+                    ms[ia..ia + width].cmp(&ms[ib..ib + width])
+                } else {
+                    let va = sketcher.get_ms_minimizer_via_plaintext(seq, ia).unwrap();
+                    let vb = sketcher.get_ms_minimizer_via_plaintext(seq, ib).unwrap();
+                    va.cmp(&vb)
+                }
+            }
+        }
+    }
+
+    /// Narrow `[lo, hi)` — a suffix-array range whose entries already agree
+    /// with `pattern[..j*width]` — to the sub-range whose `j`-th minimizer
+    /// equals `pattern`'s, via a lower-bound and an upper-bound binary
+    /// search within the range. `lower == upper` means no entry in range
+    /// has this minimizer.
+    fn narrow_bounds<SV: SeqVec>(
+        &self,
+        seq: SV::Seq<'_>,
+        pattern: &[u8],
+        lo: Sa,
+        hi: Sa,
+        j: usize,
+        sketcher: &dyn Sketcher<SV>,
+    ) -> (Sa, Sa) {
+        let sa = self.sa.as_slice();
+        let mut l = lo;
+        let mut r = hi;
+        while l < r {
+            let mid = l + (r - l).half();
+            if self.nth_minimizer_vs_pattern(seq, pattern, sa[mid.to_usize()], j, sketcher).is_lt() {
+                l = mid + Sa::ONE;
+            } else {
+                r = mid;
+            }
+        }
+        let lower = l;
+        let mut l = lower;
+        let mut r = hi;
+        while l < r {
+            let mid = l + (r - l).half();
+            if self.nth_minimizer_vs_pattern(seq, pattern, sa[mid.to_usize()], j, sketcher).is_gt() {
+                r = mid;
+            } else {
+                l = mid + Sa::ONE;
+            }
+        }
+        (lower, l)
+    }
+
+    /// Additional binary search used by [`Self::query_approx`] on a
+    /// mismatch: `[lo, hi_exclusive)` ends with a run of entries sharing
+    /// `rep`'s `j`-th minimizer; find where that run begins.
+    fn run_start<SV: SeqVec>(
+        &self,
+        seq: SV::Seq<'_>,
+        lo: Sa,
+        hi_exclusive: Sa,
+        j: usize,
+        rep: Sa,
+        sketcher: &dyn Sketcher<SV>,
+    ) -> Sa {
+        let sa = self.sa.as_slice();
+        let mut l = lo;
+        let mut r = hi_exclusive;
+        while l < r {
+            let mid = l + (r - l).half();
+            if self.nth_minimizer_vs_suffix(seq, sa[mid.to_usize()], rep, j, sketcher).is_lt() {
+                l = mid + Sa::ONE;
+            } else {
+                r = mid;
+            }
+        }
+        l
+    }
+
+    /// Additional binary search used by [`Self::query_approx`] on a
+    /// mismatch: `[lo_inclusive, hi)` begins with a run of entries sharing
+    /// `rep`'s `j`-th minimizer; find where that run ends.
+    fn run_end<SV: SeqVec>(
+        &self,
+        seq: SV::Seq<'_>,
+        lo_inclusive: Sa,
+        hi: Sa,
+        j: usize,
+        rep: Sa,
+        sketcher: &dyn Sketcher<SV>,
+    ) -> Sa {
+        let sa = self.sa.as_slice();
+        let mut l = lo_inclusive;
+        let mut r = hi;
+        while l < r {
+            let mid = l + (r - l).half();
+            if self.nth_minimizer_vs_suffix(seq, sa[mid.to_usize()], rep, j, sketcher).is_gt() {
+                r = mid;
+            } else {
+                l = mid + Sa::ONE;
+            }
+        }
+        l
+    }
+
+    /// Recursive descent behind [`Self::query_approx`]: `[lo, hi)` is the
+    /// suffix-array range of entries agreeing with `pattern[..j*width]`,
+    /// reached after spending `mismatches` minimizer substitutions so far.
+    ///
+    /// At each `j`, try to narrow `[lo, hi)` to the entries whose `j`-th
+    /// minimizer exactly matches `pattern`'s. When none do (a mismatch),
+    /// instead of pruning, spend one of the `budget` remaining mismatches
+    /// and recurse into the runs of the two nearest neighbouring minimizer
+    /// values (found via [`Self::run_start`]/[`Self::run_end`]) rather than
+    /// every possible substitute value, bounding the branching factor to 2
+    /// per mismatch so the total work stays within `O(2^budget)` descents.
+    fn search_approx<SV: SeqVec>(
+        &self,
+        seq: SV::Seq<'_>,
+        pattern: &[u8],
+        lo: Sa,
+        hi: Sa,
+        j: usize,
+        budget: usize,
+        mismatches: usize,
+        sketcher: &dyn Sketcher<SV>,
+        results: &mut std::collections::HashMap<usize, usize>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let width = sketcher.width();
+        let num_minimizers = pattern.len() / width;
+        if j == num_minimizers {
+            let sa = self.sa.as_slice();
+            for idx in lo.to_usize()..hi.to_usize() {
+                let pos = sa[idx].to_usize();
+                results
+                    .entry(pos)
+                    .and_modify(|m| *m = (*m).min(mismatches))
+                    .or_insert(mismatches);
+            }
+            return;
+        }
+
+        let (lower, upper) = self.narrow_bounds(seq, pattern, lo, hi, j, sketcher);
+        if lower < upper {
+            // Exact match at this position: keep descending without
+            // spending a mismatch.
+            self.search_approx(seq, pattern, lower, upper, j + 1, budget, mismatches, sketcher, results);
+            return;
+        }
+
+        if budget == 0 {
+            return;
+        }
+        let sa = self.sa.as_slice();
+        if lower > lo {
+            let pred = sa[(lower - Sa::ONE).to_usize()];
+            let start = self.run_start(seq, lo, lower, j, pred, sketcher);
+            self.search_approx(seq, pattern, start, lower, j + 1, budget - 1, mismatches + 1, sketcher, results);
+        }
+        if upper < hi {
+            let succ = sa[upper.to_usize()];
+            let end = self.run_end(seq, upper, hi, j, succ, sketcher);
+            self.search_approx(seq, pattern, upper, end, j + 1, budget - 1, mismatches + 1, sketcher, results);
+        }
+    }
+
+    /// Approximate search tolerating up to `max_mismatches` whole-minimizer
+    /// substitutions, for noisy long-read seeding directly in minimizer
+    /// space where an exact-only [`Self::sa_search`] would miss every seed
+    /// that straddles a sequencing error.
+    ///
+    /// `pattern.len()` must be a multiple of `sketcher.width()`. Yields
+    /// deduplicated `(pos, mismatches)` pairs, keeping the fewest
+    /// mismatches found for a given `pos`.
+    pub fn query_approx<SV: SeqVec>(
+        &self,
+        pattern: &[u8],
+        seq: SV::Seq<'_>,
+        sketcher: &dyn Sketcher<SV>,
+        max_mismatches: usize,
+    ) -> Vec<(usize, usize)> {
+        let sa = self.sa.as_slice();
+        let width = sketcher.width();
+        if sa.is_empty() || sketcher.len() == 0 || pattern.is_empty() || width == 0 || pattern.len() % width != 0 {
+            return Vec::new();
+        }
+
+        let mut results = std::collections::HashMap::new();
+        self.search_approx(
+            seq,
+            pattern,
+            Sa::ZERO,
+            Sa::from_usize(sa.len()),
+            0,
+            max_mismatches,
+            0,
+            sketcher,
+            &mut results,
+        );
+
+        let mut out: Vec<(usize, usize)> = results.into_iter().collect();
+        out.sort_unstable();
+        out
+    }
 }
 
-impl Index for SuffixArray {
+impl<SV: SeqVec, Sa: SaInt> Index<SV> for SuffixArray<Sa> {
     fn query<'s>(
         &'s self,
         pattern: &[u8],
-        seq: impl Seq<'s>,
-        sketcher: &impl Sketcher,
+        seq: SV::Seq<'s>,
+        sketcher: &dyn Sketcher<SV>,
     ) -> Box<dyn Iterator<Item = usize> + 's> {
         let (pos, cnt) = self.sa_search(sketcher, seq, pattern);
-        return Box::new((pos..pos + cnt).map(move |i| self.sa[i as usize] as usize));
+        let (pos, cnt) = (pos.to_usize(), cnt.to_usize());
+        let sa = self.sa.as_slice();
+        return Box::new((pos..pos + cnt).map(move |i| sa[i].to_usize()));
+    }
+
+    fn query_into(
+        &self,
+        pattern: &[u8],
+        seq: SV::Seq<'_>,
+        sketcher: &dyn Sketcher<SV>,
+        out: &mut Vec<usize>,
+    ) -> usize {
+        let (pos, cnt) = self.sa_search(sketcher, seq, pattern);
+        let (pos, cnt) = (pos.to_usize(), cnt.to_usize());
+        let sa = self.sa.as_slice();
+        out.extend((pos..pos + cnt).map(|i| sa[i].to_usize()));
+        cnt
+    }
+
+    fn count(&self, pattern: &[u8], seq: SV::Seq<'_>, sketcher: &dyn Sketcher<SV>) -> usize {
+        let (_pos, cnt) = self.sa_search(sketcher, seq, pattern);
+        cnt.to_usize()
+    }
+
+    fn save(&self, path: &std::path::Path) -> std::io::Result<()> {
+        SuffixArray::save(self, path)
     }
 }
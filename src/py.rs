@@ -75,7 +75,10 @@ pub fn build_plain_fm(seq: SV, sa_sampling: usize) -> PyResult<PyUIndex> {
     Ok(PyUIndex(UIndex::build(
         seq,
         SketcherBuilderEnum::IdentityParams(IdentityParams),
-        IndexBuilderEnum::FmAwry(FmAwryParams { sa_sampling }),
+        IndexBuilderEnum::FmAwry(FmAwryParams {
+            sa_sampling,
+            scratch_dir: std::env::temp_dir(),
+        }),
     )))
 }
 
@@ -97,10 +100,51 @@ pub fn build_minimized_fm(
             cacheline_ef,
             skip_zero: false,
         }),
-        IndexBuilderEnum::FmAwry(FmAwryParams { sa_sampling }),
+        IndexBuilderEnum::FmAwry(FmAwryParams {
+            sa_sampling,
+            scratch_dir: std::env::temp_dir(),
+        }),
     )))
 }
 
+#[pyfunction]
+pub fn save(uindex: &PyUIndex, dir: &str) -> PyResult<()> {
+    uindex
+        .0
+        .save(std::path::Path::new(dir))
+        .map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))
+}
+
+/// Reload a `UIndex` previously written by [`save`], built with
+/// [`build_minimized`] using the same `k`/`l`/`remap`/`cacheline_ef`.
+#[pyfunction]
+pub fn load(
+    dir: &str,
+    seq: SV,
+    k: usize,
+    l: usize,
+    remap: bool,
+    cacheline_ef: bool,
+) -> PyResult<PyUIndex> {
+    let uindex = UIndex::load(
+        std::path::Path::new(dir),
+        seq,
+        SketcherBuilderEnum::Minimizer(MinimizerParams {
+            k,
+            l,
+            remap,
+            cacheline_ef,
+            skip_zero: false,
+        }),
+        IndexBuilderEnum::DivSufSortSa(DivSufSortSa {
+            store_ms_seq: false,
+            compress: false,
+        }),
+    )
+    .map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))?;
+    Ok(PyUIndex(uindex))
+}
+
 #[pyfunction]
 pub fn gen_queries(seq: SV, len: usize, count: usize) -> PyResult<Vec<(usize, usize)>> {
     Ok(gen_query_positions(seq.as_slice(), len, count))
@@ -137,6 +181,8 @@ fn uindex(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(build_minimized, m)?)?;
     m.add_function(wrap_pyfunction!(build_plain_fm, m)?)?;
     m.add_function(wrap_pyfunction!(build_minimized_fm, m)?)?;
+    m.add_function(wrap_pyfunction!(self::save, m)?)?;
+    m.add_function(wrap_pyfunction!(self::load, m)?)?;
     m.add_function(wrap_pyfunction!(gen_queries, m)?)?;
     m.add_function(wrap_pyfunction!(self::bench, m)?)?;
     m.add_function(wrap_pyfunction!(self::bench_sindex, m)?)?;
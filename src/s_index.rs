@@ -1,33 +1,109 @@
 //! This file is a crime; mostly copied from `u_index.rs` and `suffix_array.rs`.
 //! But this uses slightly different types and modifying all the trais was pain.
 
-use std::{cell::RefCell, collections::HashMap};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{self, Read, Write},
+    path::Path,
+};
 
 use crate::{
     indices::IndexBuilderEnum,
     sketchers::{MinimizerParams, SketcherBuilderEnum},
     traits::*,
     utils::*,
-    QueryStats,
+    QueryStats, QueryThroughput, Strand,
 };
 use crate::{utils::Stats, Sketcher};
 use itertools::Itertools;
-use mem_dbg::{MemDbg, MemSize, SizeFlags};
+use mem_dbg::{MemSize, SizeFlags};
 use packed_seq::Seq;
 use packed_seq::*;
 use std::cmp::Ordering;
 use sux::traits::SuccUnchecked;
 use tracing::trace;
 
-#[derive(MemSize)]
+/// Identifies an [`SIndex`] file saved by [`SIndex::save`], so loading a
+/// mismatched or unrelated file fails cleanly instead of producing garbage.
+const MAGIC: &[u8; 8] = b"UIDXSI1\0";
+
+/// Byte length of the fixed-size part of an [`SIndex`] file header (magic,
+/// `k`, `l`, scale flag + value, canonical flag, `seq` ASCII length),
+/// i.e. everything written by [`SIndex::save`] before `seq`'s ASCII bytes.
+const HEADER_LEN: usize = 8 + 8 + 8 + 1 + 8 + 1 + 8;
+
 pub struct SIndex<SV: SeqVec> {
     pub(crate) seq: SV,
     k: usize,
     l: usize,
+    /// `Some(scale)` when sketched with [`SIndex::build_frac_min_hash`]
+    /// instead of the default per-window minimizer sampling; see
+    /// [`frac_min_hash_positions`].
+    scale: Option<u64>,
+    /// `true` when sketched with [`SIndex::build_canonical`]: anchor
+    /// positions were chosen by canonical (strand-symmetric) k-mer value
+    /// instead of raw forward-strand value, which is what lets
+    /// [`SIndex::query_canonical`] find reverse-complement occurrences from
+    /// a single window scan. Not consulted by [`Self::query`]/[`Self::query_into`],
+    /// which always search the forward strand regardless.
+    canonical: bool,
     ssa: SparseSuffixArray,
     pub(crate) query_stats: RefCell<QueryStats>,
     stats: Stats,
     ranges: sux::dict::elias_fano::EfDict,
+    /// The mapping `ssa`'s suffix array borrows from when loaded via
+    /// [`Self::load_mmap`]; `None` otherwise. Never read directly — it
+    /// exists only to keep the mapping alive as long as `self` does.
+    _mmap: Option<memmap2::Mmap>,
+}
+
+impl<SV: SeqVec> MemSize for SIndex<SV> {
+    /// An `mmap`-backed `ssa.sa` shares read-only pages with the file cache
+    /// instead of allocating, so (unlike an owned `Vec`) it doesn't count
+    /// towards heap usage here; see [`SparseSuffixArray`]'s own `MemSize`
+    /// impl. `seq` is always a fresh, owned allocation (see [`Self::load`]),
+    /// even when the rest of `self` was loaded via `load_mmap`.
+    fn mem_size(&self, flags: SizeFlags) -> usize {
+        self.seq.mem_size(flags) + self.ssa.mem_size(flags) + self.ranges.mem_size(flags)
+    }
+}
+
+/// Result of [`SIndex::containment`]: a FracMinHash-based estimate of how
+/// much of a query's k-mer content is present in the indexed text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Containment {
+    /// Number of distinct query anchor k-mers also present in the index.
+    pub intersection: usize,
+    /// Number of distinct anchor k-mers sampled from the query (the
+    /// denominator of `containment`).
+    pub query_sketch_size: usize,
+    /// Number of anchor k-mers retained when the index was built (an upper
+    /// bound on the number of distinct indexed k-mers).
+    pub index_sketch_size: usize,
+    /// `intersection / query_sketch_size`: the estimated fraction of the
+    /// query's sampled k-mers contained in the index, `C(query ⊆ index)`.
+    pub containment: f64,
+    /// `intersection / (query_sketch_size + index_sketch_size - intersection)`:
+    /// the estimated Jaccard similarity `J = |A∩B| / |A∪B|` of the query's
+    /// and the index's sampled k-mer sets.
+    pub jaccard: f64,
+}
+
+/// A match yielded by [`SIndex::query_located`]: the same global position
+/// [`SIndex::query`] would yield, plus which input read it falls in and the
+/// offset within that read, for callers (e.g. read-mapping workflows) that
+/// need the originating sequence rather than a flat coordinate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Located {
+    /// Index of the input read this match falls in, in build order.
+    pub read_id: usize,
+    /// Offset of the match within that read, i.e. `global_pos` minus the
+    /// read's start offset in `seq`.
+    pub offset_in_read: usize,
+    /// The same global byte position into the concatenated `seq` that
+    /// [`SIndex::query`] would yield for this match.
+    pub global_pos: usize,
 }
 
 impl<SV: SeqVec> Drop for SIndex<SV> {
@@ -41,6 +117,10 @@ impl<SV: SeqVec> Drop for SIndex<SV> {
             mismatches,
             bad_ranges,
             matches,
+            forward_matches,
+            reverse_matches,
+            approx_candidates: _,
+            approx_mismatch_histogram: _,
             mut t_sketch,
             mut t_search,
             mut t_invert_pos,
@@ -66,6 +146,8 @@ out of bounds     {out_of_bounds:>9}
 mismatches        {mismatches:>9}
 bad_ranges        {bad_ranges:>9}
 matches           {matches:>9}
+forward matches   {forward_matches:>9}
+reverse matches   {reverse_matches:>9}
 t_sketch          {t_sketch:>9} ns/query
 t_search          {t_search:>9} ns/query
 t_invert_pos      {t_invert_pos:>9} ns/query
@@ -78,18 +160,68 @@ t_ranges          {t_ranges:>9} ns/query"
 impl<SV: SeqVec> SIndex<SV> {
     /// 1. Sketch input to minimizer space.
     /// 2. Build minimizer space index.
-    pub fn build(mut seq: SV, k: usize, l: usize) -> Self {
+    pub fn build(seq: SV, k: usize, l: usize) -> Self {
+        Self::build_with_options(seq, k, l, None, false)
+    }
+
+    /// Like [`Self::build`], but samples retained k-mer start positions via
+    /// FracMinHash / scaled sampling instead of per-window minimizers: a
+    /// k-mer's position is kept, independent of its neighbors, iff
+    /// `hash(kmer) <= u64::MAX / scale` (see [`frac_min_hash_positions`]).
+    /// This gives a density-controlled, *consistent* sample — the same
+    /// k-mer is always kept or dropped everywhere it occurs — which is
+    /// useful for set-containment-style queries across reads, unlike the
+    /// windowed minimizer sampling [`Self::build`] uses. `scale` is the
+    /// downsampling factor: roughly `1 / scale` of all k-mers are kept.
+    pub fn build_frac_min_hash(seq: SV, k: usize, scale: u64) -> Self {
+        Self::build_with_options(seq, k, k, Some(scale), false)
+    }
+
+    /// Like [`Self::build`], but selects each window's anchor by *canonical*
+    /// k-mer value (the smaller of the k-mer and its reverse complement;
+    /// see [`canonical_kmer`]) instead of raw forward-strand value. A
+    /// genomic region and its reverse complement then always pick mirrored
+    /// anchor positions, so [`Self::query_canonical`] can find a
+    /// reverse-complement occurrence from a single window scan over the
+    /// query, instead of having to search the text twice.
+    pub fn build_canonical(seq: SV, k: usize, l: usize) -> Self {
+        Self::build_with_options(seq, k, l, None, true)
+    }
+
+    fn build_with_options(
+        mut seq: SV,
+        k: usize,
+        l: usize,
+        scale: Option<u64>,
+        canonical: bool,
+    ) -> Self {
         *INIT_TRACE;
         let stats = Stats::default();
         let mut timer = Timer::new_stats("Sketch", &stats);
 
-        let minimizer_positions =
-            minimizers::simd::minimizer::minimizer_simd_it::<false>(seq.as_slice(), k, l - k + 1)
-                .dedup()
-                .collect::<Vec<_>>();
+        let positions = match (scale, canonical) {
+            (None, false) => {
+                minimizers::simd::minimizer::minimizer_simd_it::<false>(seq.as_slice(), k, l - k + 1)
+                    .dedup()
+                    .collect::<Vec<_>>()
+            }
+            (None, true) => canonical_minimizer_positions(seq.as_slice(), k, l - k + 1),
+            (Some(scale), _) => {
+                let (positions, total_kmers) = frac_min_hash_positions(seq.as_slice(), k, scale);
+                stats.set("sketch_frac_min_hash_scale", scale);
+                stats.set("sketch_frac_min_hash_total_kmers", total_kmers as u64);
+                stats.set("sketch_frac_min_hash_retained", positions.len() as u64);
+                if total_kmers > 0 {
+                    stats.add(
+                        "sketch_frac_min_hash_retained_fraction",
+                        positions.len() as f32 / total_kmers as f32,
+                    );
+                }
+                positions
+            }
+        };
         timer.next("Build");
-        // TODO
-        let ssa = SparseSuffixArray::new(seq.as_slice(), minimizer_positions);
+        let ssa = SparseSuffixArray::new(seq.as_slice(), positions);
         drop(timer);
 
         // Build seq ranges.
@@ -106,10 +238,13 @@ impl<SV: SeqVec> SIndex<SV> {
             seq,
             k,
             l,
+            scale,
+            canonical,
             ssa,
             query_stats: RefCell::new(QueryStats::default()),
             stats,
             ranges: ef_ranges.build_with_dict(),
+            _mmap: None,
         };
         let seq_size = sindex.seq.mem_size(SizeFlags::default()) as f32 / 1000000.;
         sindex.stats.add("seq_size_MB", seq_size);
@@ -163,17 +298,11 @@ impl<SV: SeqVec> SIndex<SV> {
     ) -> Option<Box<dyn Iterator<Item = usize> + 'p>> {
         self.query_stats.borrow_mut().queries += 1;
         let t1 = std::time::Instant::now();
-        // TODO: Find position of first minimizer of pattern.
 
-        if pattern.len() < self.l {
+        let Some(offset) = sindex_anchor_offset(pattern, self.k, self.l, self.scale) else {
             self.query_stats.borrow_mut().too_short += 1;
             return None;
-        }
-
-        let offset = minimizers::simd::minimizer::minimizer_window_naive::<false>(
-            pattern.slice(0..self.l),
-            self.k,
-        );
+        };
 
         let t2 = std::time::Instant::now();
         self.query_stats.borrow_mut().t_sketch += t2.duration_since(t1).subsec_nanos() as usize;
@@ -224,23 +353,759 @@ impl<SV: SeqVec> SIndex<SV> {
             Some(start)
         })))
     }
+
+    /// Like [`Self::query`], but appends match positions to a caller-owned
+    /// `out` buffer instead of allocating a boxed iterator, so a single
+    /// `Vec` can be reused across many queries. Returns the number of
+    /// matches appended, or `None` if the pattern is too short to sketch.
+    pub fn query_into<'p>(
+        &'p self,
+        pattern: <SV as SeqVec>::Seq<'p>,
+        out: &mut Vec<usize>,
+    ) -> Option<usize> {
+        self.query_stats.borrow_mut().queries += 1;
+        let t1 = std::time::Instant::now();
+
+        let Some(offset) = sindex_anchor_offset(pattern, self.k, self.l, self.scale) else {
+            self.query_stats.borrow_mut().too_short += 1;
+            return None;
+        };
+
+        let t2 = std::time::Instant::now();
+        self.query_stats.borrow_mut().t_sketch += t2.duration_since(t1).subsec_nanos() as usize;
+        let mut ms_occ = Vec::new();
+        self.ssa.query_into(
+            self.seq.as_slice(),
+            pattern.slice(offset..pattern.len()),
+            &mut ms_occ,
+        );
+        let t3 = std::time::Instant::now();
+        self.query_stats.borrow_mut().t_search += t3.duration_since(t2).subsec_nanos() as usize;
+
+        let start_len = out.len();
+        for pos in ms_occ {
+            let Some(start) = pos.checked_sub(offset) else {
+                self.query_stats.borrow_mut().out_of_bounds += 1;
+                continue;
+            };
+            let end = start + pattern.len();
+            assert!(end <= self.seq.len(), "Pattern extends beyond the text");
+
+            if self.seq.slice(start..start + offset) != pattern.slice(0..offset) {
+                self.query_stats.borrow_mut().mismatches += 1;
+                continue;
+            }
+
+            let range_end = unsafe { self.ranges.succ_unchecked::<true>(start).1 };
+            if end > range_end {
+                self.query_stats.borrow_mut().bad_ranges += 1;
+                continue;
+            }
+
+            self.query_stats.borrow_mut().matches += 1;
+            out.push(start);
+        }
+        Some(out.len() - start_len)
+    }
+
+    /// Resolve a global match position — already known (by the caller, via
+    /// `ranges`) to lie entirely within a single input read — to the index
+    /// of that read and the offset within it. `ranges` stores each read's
+    /// `(start, end)` as two consecutive Elias-Fano values, so the strict
+    /// successor of `global_pos` (the least stored value greater than it)
+    /// is the end of the read containing it, at rank `2 * read_id + 1`;
+    /// `rank / 2` is therefore `read_id`.
+    fn locate(&self, global_pos: usize) -> (usize, usize) {
+        let (rank, _) = unsafe { self.ranges.succ_unchecked::<true>(global_pos) };
+        let read_id = rank / 2;
+        let read_start = self.ranges.get(read_id * 2);
+        (read_id, global_pos - read_start)
+    }
+
+    /// Like [`Self::query`], but resolves each match's global position to
+    /// the input read it falls in and the offset within that read (see
+    /// [`Located`]), instead of just the flat coordinate — useful for
+    /// read-mapping workflows where callers need the originating sequence.
+    /// Returns `None` if the pattern is too short to contain a minimizer.
+    pub fn query_located<'p>(
+        &'p self,
+        pattern: <SV as SeqVec>::Seq<'p>,
+    ) -> Option<Box<dyn Iterator<Item = Located> + 'p>> {
+        let it = self.query(pattern)?;
+        Some(Box::new(it.map(move |global_pos| {
+            let (read_id, offset_in_read) = self.locate(global_pos);
+            Located { read_id, offset_in_read, global_pos }
+        })))
+    }
+
+    /// Like [`Self::query_into`], but also matches `pattern`'s reverse
+    /// complement against the forward-strand text, tagging each hit with
+    /// the [`Strand`] it was found on and appending to `out`. Returns the
+    /// number of positions appended, or `None` if `pattern` is shorter than
+    /// the window length `l`.
+    ///
+    /// Unlike [`UIndex::query_both_strands`](crate::UIndex::query_both_strands),
+    /// which searches the forward and reverse-complement pattern as two
+    /// independent queries, this does a single window scan over `pattern`
+    /// to decide which orientation to search in: at each position of
+    /// `pattern`'s first `l` bases, the smaller of the k-mer and its
+    /// reverse complement is the *canonical* k-mer (see [`canonical_kmer`]),
+    /// and the window position with the smallest canonical value anchors
+    /// the search, exactly like [`Self::query`] does with the raw
+    /// forward-strand minimizer. Whichever of the k-mer/its reverse
+    /// complement was smaller there tells us whether `pattern` or its
+    /// reverse complement is the one actually present in the forward-strand
+    /// text, so only one of them needs to be searched.
+    ///
+    /// This only reliably finds reverse-strand hits when `self` was built
+    /// with [`Self::build_canonical`]: only then is a genomic region's
+    /// anchor position guaranteed to match the one its reverse complement
+    /// would pick.
+    pub fn query_canonical_into(
+        &self,
+        pattern: <SV as SeqVec>::Seq<'_>,
+        out: &mut Vec<(usize, Strand)>,
+    ) -> Option<usize> {
+        self.query_stats.borrow_mut().queries += 1;
+
+        let Some((pos, strand)) = canonical_minimizer_window(pattern, self.k, self.l) else {
+            self.query_stats.borrow_mut().too_short += 1;
+            return None;
+        };
+
+        // In the `Reverse` case, `pattern`'s reverse complement is what
+        // actually occurs in the forward-strand text, so that's what we
+        // search and verify against; `rc_pattern` just needs to outlive the
+        // rest of this call.
+        let rc_pattern: Option<SV> = match strand {
+            Strand::Forward => None,
+            Strand::Reverse => Some(pattern.revcomp()),
+        };
+        let (verify_pattern, offset) = match &rc_pattern {
+            None => (pattern, pos),
+            Some(rc) => (rc.as_slice(), pattern.len() - self.k - pos),
+        };
+
+        let mut ms_occ = Vec::new();
+        self.ssa.query_into(
+            self.seq.as_slice(),
+            verify_pattern.slice(offset..verify_pattern.len()),
+            &mut ms_occ,
+        );
+
+        let start_len = out.len();
+        for p in ms_occ {
+            let Some(start) = p.checked_sub(offset) else {
+                self.query_stats.borrow_mut().out_of_bounds += 1;
+                continue;
+            };
+            let end = start + verify_pattern.len();
+            if end > self.seq.len() {
+                self.query_stats.borrow_mut().out_of_bounds += 1;
+                continue;
+            }
+
+            if self.seq.slice(start..start + offset) != verify_pattern.slice(0..offset) {
+                self.query_stats.borrow_mut().mismatches += 1;
+                continue;
+            }
+
+            let range_end = unsafe { self.ranges.succ_unchecked::<true>(start).1 };
+            if end > range_end {
+                self.query_stats.borrow_mut().bad_ranges += 1;
+                continue;
+            }
+
+            let mut stats = self.query_stats.borrow_mut();
+            stats.matches += 1;
+            match strand {
+                Strand::Forward => stats.forward_matches += 1,
+                Strand::Reverse => stats.reverse_matches += 1,
+            }
+            drop(stats);
+            out.push((start, strand));
+        }
+        Some(out.len() - start_len)
+    }
+
+    /// Like [`Self::query_canonical_into`], but allocates and returns a
+    /// fresh `Vec` instead of appending to a caller-owned one.
+    pub fn query_canonical(
+        &self,
+        pattern: <SV as SeqVec>::Seq<'_>,
+    ) -> Option<Vec<(usize, Strand)>> {
+        let mut out = Vec::new();
+        self.query_canonical_into(pattern, &mut out)?;
+        Some(out)
+    }
+
+    /// Estimate the fraction of `pattern`'s FracMinHash-sampled k-mers that
+    /// are present in the indexed text (set containment `C(query ⊆
+    /// index)`), instead of locating exact match positions. Requires
+    /// `self` to have been built with [`Self::build_frac_min_hash`] (its
+    /// `scale` is reused to sketch `pattern` too): because FracMinHash
+    /// sampling is *consistent* — the same k-mer is always kept or dropped,
+    /// independent of its neighbors — the sampled fraction of `pattern`'s
+    /// k-mers present in the index is an unbiased estimator of the true
+    /// containment, and combined with the two sketch sizes also gives a
+    /// Jaccard similarity estimate. Returns `None` if `self` wasn't built
+    /// with a scale, or `pattern` is too short to contain a single k-mer,
+    /// or none of `pattern`'s k-mers passes the FracMinHash threshold.
+    pub fn containment(&self, pattern: SV::Seq<'_>) -> Option<Containment> {
+        let scale = self.scale?;
+        self.query_stats.borrow_mut().queries += 1;
+        let t0 = std::time::Instant::now();
+
+        let (anchor_positions, _total_kmers) = frac_min_hash_positions(pattern, self.k, scale);
+        if anchor_positions.is_empty() {
+            self.query_stats.borrow_mut().too_short += 1;
+            return None;
+        }
+
+        // Dedup by k-mer value (not position): a repeated k-mer in `pattern`
+        // must count once towards the containment/Jaccard denominators.
+        let mut kmers: Vec<(u64, u32)> = anchor_positions
+            .iter()
+            .map(|&pos| {
+                let word = pattern.slice(pos as usize..pos as usize + self.k).to_word() as u64;
+                (word, pos)
+            })
+            .collect();
+        kmers.sort_unstable_by_key(|&(word, _)| word);
+        kmers.dedup_by_key(|&mut (word, _)| word);
+
+        let t1 = std::time::Instant::now();
+        self.query_stats.borrow_mut().t_sketch += t1.duration_since(t0).subsec_nanos() as usize;
+
+        let query_sketch_size = kmers.len();
+        let mut intersection = 0;
+        for (_, pos) in &kmers {
+            let kmer = pattern.slice(*pos as usize..*pos as usize + self.k);
+            let (_, cnt) = self.ssa.sa_search(self.seq.as_slice(), kmer);
+            if cnt > 0 {
+                intersection += 1;
+            }
+        }
+
+        let t2 = std::time::Instant::now();
+        self.query_stats.borrow_mut().t_search += t2.duration_since(t1).subsec_nanos() as usize;
+
+        // Anchors retained in the text at build time; an upper bound on the
+        // number of *distinct* indexed k-mers (duplicate k-mers across the
+        // text aren't deduplicated here).
+        let index_sketch_size = self.ssa.sa.as_slice().len();
+        let union = query_sketch_size + index_sketch_size - intersection;
+        Some(Containment {
+            intersection,
+            query_sketch_size,
+            index_sketch_size,
+            containment: intersection as f64 / query_sketch_size as f64,
+            jaccard: if union == 0 {
+                0.0
+            } else {
+                intersection as f64 / union as f64
+            },
+        })
+    }
+
+    /// Persist this index to a single file at `path`, so a later
+    /// [`Self::load`]/[`Self::load_mmap`] can reconstruct it without
+    /// re-running [`Self::build`] — in particular without re-sorting the
+    /// sparse suffix array or rebuilding the Elias-Fano `ranges` dictionary,
+    /// the two expensive parts of building.
+    ///
+    /// Only `ssa`'s suffix array gets genuine zero-copy treatment on load
+    /// (via [`Self::load_mmap`]): it's written at a 4-byte-aligned file
+    /// offset so it can be reinterpreted in place as `&[u32]`. `seq` is
+    /// written as a plain ASCII round-trip ([`SeqVec::from_ascii`] on load)
+    /// rather than a packed-byte reinterpretation, since this crate's
+    /// `SeqVec` trait has no accessor for a raw packed buffer; `ranges` is
+    /// re-parsed from its Elias-Fano on-disk encoding on every load. Loaded
+    /// indices always start with a fresh `QueryStats`/`Stats` rather than
+    /// persisting the old ones, matching the fact that those are
+    /// process-lifetime counters, not index state.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+
+        w.write_all(MAGIC)?;
+        w.write_all(&(self.k as u64).to_le_bytes())?;
+        w.write_all(&(self.l as u64).to_le_bytes())?;
+        w.write_all(&[self.scale.is_some() as u8])?;
+        w.write_all(&self.scale.unwrap_or(0).to_le_bytes())?;
+        w.write_all(&[self.canonical as u8])?;
+
+        let seq_ascii = self.seq.as_slice().unpack();
+        w.write_all(&(seq_ascii.len() as u64).to_le_bytes())?;
+        w.write_all(&seq_ascii)?;
+
+        let pos_before_sa = HEADER_LEN + seq_ascii.len();
+        SparseSuffixArray::write_sa(self.ssa.sa.as_slice(), pos_before_sa, &mut w)?;
+
+        #[cfg(feature = "serde")]
+        {
+            use epserde::ser::Serialize;
+            self.ranges
+                .serialize(&mut w)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        }
+        #[cfg(not(feature = "serde"))]
+        {
+            w.write_all(&(self.ranges.len() as u64).to_le_bytes())?;
+            for i in 0..self.ranges.len() {
+                w.write_all(&(self.ranges.get(i) as u64).to_le_bytes())?;
+            }
+        }
+
+        w.flush()
+    }
+
+    /// Read an `SIndex` previously written by [`Self::save`], copying `seq`
+    /// and the suffix array into freshly allocated buffers.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut r = io::BufReader::new(std::fs::File::open(path)?);
+        let (k, l, scale, canonical, seq_ascii) = Self::read_header_and_seq(&mut r)?;
+
+        let pos_before_sa = HEADER_LEN + seq_ascii.len();
+        let sa = SparseSuffixArray::read_sa(pos_before_sa, &mut r)?;
+        let ranges = Self::read_ranges(&mut r)?;
+
+        Ok(Self {
+            seq: SV::from_ascii(&seq_ascii),
+            k,
+            l,
+            scale,
+            canonical,
+            ssa: SparseSuffixArray { sa: SaStorage::Owned(sa) },
+            query_stats: RefCell::new(QueryStats::default()),
+            stats: Stats::default(),
+            ranges,
+            _mmap: None,
+        })
+    }
+
+    /// Like [`Self::load`], but memory-maps the file read-only and borrows
+    /// the suffix array directly from the mapping instead of copying it
+    /// into the heap, so a large index can be reloaded without its
+    /// resident pages showing up as a fresh allocation. `seq` is still
+    /// reconstructed into an owned buffer (see [`Self::save`]).
+    pub fn load_mmap(path: &Path) -> io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        let mut cursor = io::Cursor::new(&mmap[..]);
+        let (k, l, scale, canonical, seq_ascii_len) = Self::read_header(&mut cursor)?;
+
+        let seq_start = HEADER_LEN;
+        let seq_ascii = &mmap[seq_start..seq_start + seq_ascii_len];
+        let seq = SV::from_ascii(seq_ascii);
+
+        let pos_before_sa = HEADER_LEN + seq_ascii_len;
+        let mut len_buf = [0u8; 8];
+        len_buf.copy_from_slice(&mmap[pos_before_sa..pos_before_sa + 8]);
+        let sa_len = u64::from_le_bytes(len_buf) as usize;
+        let pos_after_len = pos_before_sa + 8;
+        let pad = (4 - pos_after_len % 4) % 4;
+        let sa_start = pos_after_len + pad;
+        let sa_bytes = &mmap[sa_start..sa_start + sa_len * 4];
+        // SAFETY: `sa_bytes` is exactly `sa_len` little-endian `u32`s
+        // written by `save` at a file offset explicitly padded to a
+        // multiple of 4 bytes, and `mmap`-backed pages are themselves
+        // page- (hence 4-byte-) aligned, so `sa_bytes` is aligned. The
+        // slice borrows from `mmap`, which outlives it as `self._mmap` for
+        // as long as `self` (and thus this `SaStorage::Mapped`) is alive.
+        let sa: &'static [u32] = unsafe {
+            let aligned = sa_bytes.align_to::<u32>().1;
+            std::slice::from_raw_parts(aligned.as_ptr(), aligned.len())
+        };
+
+        let mut ranges_cursor = io::Cursor::new(&mmap[sa_start + sa_len * 4..]);
+        let ranges = Self::read_ranges(&mut ranges_cursor)?;
+
+        Ok(Self {
+            seq,
+            k,
+            l,
+            scale,
+            canonical,
+            ssa: SparseSuffixArray { sa: SaStorage::Mapped(sa) },
+            query_stats: RefCell::new(QueryStats::default()),
+            stats: Stats::default(),
+            ranges,
+            _mmap: Some(mmap),
+        })
+    }
+
+    /// Read the fixed-size header fields (everything before `seq`'s ASCII
+    /// bytes), leaving the reader positioned right at the start of `seq`.
+    fn read_header(r: &mut impl Read) -> io::Result<(usize, usize, Option<u64>, bool, usize)> {
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not an SIndex file (magic mismatch)",
+            ));
+        }
+        let mut buf8 = [0u8; 8];
+        r.read_exact(&mut buf8)?;
+        let k = u64::from_le_bytes(buf8) as usize;
+        r.read_exact(&mut buf8)?;
+        let l = u64::from_le_bytes(buf8) as usize;
+        let mut has_scale = [0u8; 1];
+        r.read_exact(&mut has_scale)?;
+        r.read_exact(&mut buf8)?;
+        let scale_val = u64::from_le_bytes(buf8);
+        let scale = (has_scale[0] != 0).then_some(scale_val);
+        let mut canonical = [0u8; 1];
+        r.read_exact(&mut canonical)?;
+        r.read_exact(&mut buf8)?;
+        let seq_ascii_len = u64::from_le_bytes(buf8) as usize;
+        Ok((k, l, scale, canonical[0] != 0, seq_ascii_len))
+    }
+
+    /// Like [`Self::read_header`], but also reads `seq`'s ASCII bytes that
+    /// immediately follow it, for the streaming (non-mmap) [`Self::load`].
+    fn read_header_and_seq(
+        r: &mut impl Read,
+    ) -> io::Result<(usize, usize, Option<u64>, bool, Vec<u8>)> {
+        let (k, l, scale, canonical, seq_ascii_len) = Self::read_header(r)?;
+        let mut seq_ascii = vec![0u8; seq_ascii_len];
+        r.read_exact(&mut seq_ascii)?;
+        Ok((k, l, scale, canonical, seq_ascii))
+    }
+
+    /// Read the `ranges` Elias-Fano dictionary written by [`Self::save`].
+    fn read_ranges(r: &mut impl Read) -> io::Result<sux::dict::elias_fano::EfDict> {
+        #[cfg(feature = "serde")]
+        {
+            use epserde::deser::Deserialize;
+            sux::dict::elias_fano::EfDict::deserialize_full(r)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+        }
+        #[cfg(not(feature = "serde"))]
+        {
+            let mut buf8 = [0u8; 8];
+            r.read_exact(&mut buf8)?;
+            let len = u64::from_le_bytes(buf8) as usize;
+            let mut values = Vec::with_capacity(len);
+            for _ in 0..len {
+                r.read_exact(&mut buf8)?;
+                values.push(u64::from_le_bytes(buf8) as usize);
+            }
+            let mut ef_ranges =
+                sux::dict::elias_fano::EliasFanoBuilder::new(len, *values.last().unwrap_or(&0));
+            for v in values {
+                ef_ranges.push(v);
+            }
+            Ok(ef_ranges.build_with_dict())
+        }
+    }
+
+    /// Like [`Self::query_into`] run once per query in `queries`, but
+    /// partitioned across `num_threads` threads instead of run serially.
+    /// Bypasses `query_stats` bookkeeping (see `sindex_query_match_count`),
+    /// the same trade-off `UIndex::bench_parallel` makes, for the same
+    /// reason: `RefCell`-based stats can't be shared across threads.
+    pub fn bench_parallel(&self, queries: &[SV], num_threads: usize) -> QueryThroughput
+    where
+        SV: Sync,
+    {
+        let num_threads = num_threads.max(1);
+        let seq = &self.seq;
+        let ssa = &self.ssa;
+        let ranges = &self.ranges;
+        let k = self.k;
+        let l = self.l;
+        let scale = self.scale;
+
+        let chunk_size = queries.len().div_ceil(num_threads).max(1);
+        let start = std::time::Instant::now();
+        let latencies: Vec<std::time::Duration> = std::thread::scope(|scope| {
+            queries
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        let mut latencies = Vec::with_capacity(chunk.len());
+                        for q in chunk {
+                            let t0 = std::time::Instant::now();
+                            std::hint::black_box(sindex_query_match_count(
+                                seq,
+                                ssa,
+                                ranges,
+                                k,
+                                l,
+                                scale,
+                                q.as_slice(),
+                            ));
+                            latencies.push(t0.elapsed());
+                        }
+                        latencies
+                    })
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect()
+        });
+        let elapsed = start.elapsed();
+
+        QueryThroughput::from_latencies(queries.len(), elapsed, latencies)
+    }
+}
+
+/// The part of [`SIndex::query_into`] that doesn't touch `query_stats`,
+/// factored out so [`SIndex::bench_parallel`] can call it with borrowed
+/// `seq`/`ssa`/`ranges` instead of `&self`.
+fn sindex_query_match_count<SV: SeqVec>(
+    seq: &SV,
+    ssa: &SparseSuffixArray,
+    ranges: &sux::dict::elias_fano::EfDict,
+    k: usize,
+    l: usize,
+    scale: Option<u64>,
+    pattern: SV::Seq<'_>,
+) -> usize {
+    let Some(offset) = sindex_anchor_offset(pattern, k, l, scale) else {
+        return 0;
+    };
+
+    let mut ms_occ = Vec::new();
+    ssa.query_into(seq.as_slice(), pattern.slice(offset..pattern.len()), &mut ms_occ);
+
+    let mut count = 0;
+    for pos in ms_occ {
+        let Some(start) = pos.checked_sub(offset) else {
+            continue;
+        };
+        let end = start + pattern.len();
+        if end > seq.len() {
+            continue;
+        }
+        if seq.slice(start..start + offset) != pattern.slice(0..offset) {
+            continue;
+        }
+        let range_end = unsafe { ranges.succ_unchecked::<true>(start).1 };
+        if end > range_end {
+            continue;
+        }
+        count += 1;
+    }
+    count
+}
+
+/// Find the anchor offset used to seed a pattern's search in an
+/// [`SIndex`]'s [`SparseSuffixArray`]: the position, within `pattern`, of
+/// the same kind of k-mer the index itself was sketched with. With
+/// `scale: None` (the default windowed-minimizer mode) that's `pattern`'s
+/// first window minimizer; with `scale: Some(_)` (see
+/// [`SIndex::build_frac_min_hash`]) it's `pattern`'s first
+/// FracMinHash-retained k-mer, found by scanning left to right. Returns
+/// `None` when no such position exists: `pattern` shorter than the
+/// minimum window/k-mer length, or (scaled mode only) no k-mer in
+/// `pattern` happens to pass the FracMinHash threshold.
+fn sindex_anchor_offset<'i>(
+    pattern: impl Seq<'i>,
+    k: usize,
+    l: usize,
+    scale: Option<u64>,
+) -> Option<usize> {
+    match scale {
+        None => {
+            if pattern.len() < l {
+                return None;
+            }
+            Some(minimizers::simd::minimizer::minimizer_window_naive::<false>(
+                pattern.slice(0..l),
+                k,
+            ))
+        }
+        Some(scale) => {
+            if pattern.len() < k {
+                return None;
+            }
+            let threshold = u64::MAX / scale.max(1);
+            (0..=pattern.len() - k).find(|&pos| {
+                let kmer = canonical_kmer(pattern.slice(pos..pos + k).to_word() as u64, k);
+                frac_min_hash(kmer) <= threshold
+            })
+        }
+    }
+}
+
+/// A fast, fixed-seed 64-bit hash (the `splitmix64` finalizer) used by
+/// [`frac_min_hash_positions`]/[`sindex_anchor_offset`] for FracMinHash /
+/// scaled k-mer sampling. Determinism across calls (no process-randomized
+/// seed, unlike e.g. `RandomState`) is the load-bearing property: indexing
+/// and querying must retain the exact same k-mers, so the same k-mer must
+/// hash identically every time, everywhere.
+fn frac_min_hash(kmer: u64) -> u64 {
+    let mut h = kmer.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    h ^ (h >> 31)
+}
+
+/// FracMinHash / scaled sampling of `seq`'s k-mer start positions: a
+/// k-mer's position is kept iff `frac_min_hash(canonical_kmer(kmer)) <=
+/// u64::MAX / scale`, giving a density-controlled, *consistent* sample
+/// (independent of neighboring k-mers, unlike windowed minimizers) — roughly
+/// `1 / scale` of all k-mers are kept. Hashing the canonical form (see
+/// [`canonical_kmer`]), not the raw forward-strand k-mer, means a k-mer is
+/// retained exactly when its reverse complement is. [`SIndex::containment`]
+/// still looks each retained query k-mer up in its forward orientation
+/// only, so reverse-strand occurrences are not counted. Returns the retained
+/// positions plus the total number of k-mers considered, for the
+/// retained-fraction stat logged by [`SIndex::build_frac_min_hash`].
+fn frac_min_hash_positions<'i>(seq: impl Seq<'i>, k: usize, scale: u64) -> (Vec<u32>, usize) {
+    let threshold = u64::MAX / scale.max(1);
+    let n = seq.len();
+    let mut positions = Vec::new();
+    let mut total_kmers = 0;
+    if n >= k {
+        for pos in 0..=n - k {
+            total_kmers += 1;
+            let kmer = canonical_kmer(seq.slice(pos..pos + k).to_word() as u64, k);
+            if frac_min_hash(kmer) <= threshold {
+                positions.push(pos as u32);
+            }
+        }
+    }
+    (positions, total_kmers)
+}
+
+/// Reverse-complement a 2-bit-packed k-mer word produced by `Seq::to_word`:
+/// reverses the order of the `k` 2-bit base codes and complements each one
+/// (`code ^ 0b11`). Relies on this crate's 2-bit encoding pairing
+/// complementary bases as bit-complements of one another (the standard
+/// A/C/G/T = `00`/`01`/`10`/`11` encoding, where A/T and C/G are `00`/`11`
+/// and `01`/`10`).
+fn revcomp_kmer(kmer: u64, k: usize) -> u64 {
+    debug_assert!(k <= 32, "a k-mer must fit in a 64-bit word to use to_word()");
+    let mut kmer = kmer;
+    let mut rc = 0u64;
+    for _ in 0..k {
+        rc = (rc << 2) | ((kmer & 0b11) ^ 0b11);
+        kmer >>= 2;
+    }
+    rc
+}
+
+/// The canonical representative of a k-mer: the numerically smaller of
+/// `kmer` and its reverse complement (see [`revcomp_kmer`]), so that a
+/// sequence and its reverse complement always agree on which k-mer value
+/// represents a given window, regardless of which strand was sequenced.
+/// The basis of [`SIndex::build_canonical`]/[`canonical_minimizer_window`].
+fn canonical_kmer(kmer: u64, k: usize) -> u64 {
+    kmer.min(revcomp_kmer(kmer, k))
+}
+
+/// Build-time counterpart of [`canonical_minimizer_window`]: like the
+/// default windowed-minimizer sampling `SIndex::build` uses, but selects
+/// each window's anchor by *canonical* k-mer value (see [`canonical_kmer`])
+/// instead of raw forward-strand value, consecutive duplicate positions
+/// collapsed the same way `.dedup()` does for the non-canonical path.
+/// Written as a plain scalar scan rather than reusing
+/// `minimizer_simd_it`, since canonicalization needs each k-mer's reverse
+/// complement, which that SIMD iterator has no way to return.
+fn canonical_minimizer_positions<'i>(seq: impl Seq<'i>, k: usize, w: usize) -> Vec<u32> {
+    let n = seq.len();
+    if n < k {
+        return Vec::new();
+    }
+    let num_kmers = n - k + 1;
+    let canon: Vec<u64> = (0..num_kmers)
+        .map(|pos| canonical_kmer(seq.slice(pos..pos + k).to_word() as u64, k))
+        .collect();
+
+    let mut positions = Vec::new();
+    let mut last = usize::MAX;
+    for win_start in 0..=num_kmers.saturating_sub(w) {
+        let win_end = win_start + w;
+        let min_pos = (win_start..win_end).min_by_key(|&i| canon[i]).unwrap();
+        if min_pos != last {
+            positions.push(min_pos as u32);
+            last = min_pos;
+        }
+    }
+    positions
+}
+
+/// Query-time counterpart of [`canonical_minimizer_positions`]: over
+/// `pattern`'s first `l` bases, finds the position whose canonical k-mer
+/// value (see [`canonical_kmer`]) is smallest, and whether the forward or
+/// reverse-complement k-mer was the canonical one there. Used by
+/// [`SIndex::query_canonical_into`] to pick, with a single window scan,
+/// which orientation of `pattern` to search the (canonically built) index
+/// with. Returns `None` when `pattern` is shorter than `l`.
+fn canonical_minimizer_window(pattern: impl Seq<'_>, k: usize, l: usize) -> Option<(usize, Strand)> {
+    if pattern.len() < l {
+        return None;
+    }
+    let w = l - k + 1;
+    let mut best_pos = 0;
+    let mut best_val = u64::MAX;
+    let mut best_strand = Strand::Forward;
+    for pos in 0..w {
+        let fwd = pattern.slice(pos..pos + k).to_word() as u64;
+        let rc = revcomp_kmer(fwd, k);
+        let (val, strand) = if rc < fwd {
+            (rc, Strand::Reverse)
+        } else {
+            (fwd, Strand::Forward)
+        };
+        if val < best_val {
+            best_val = val;
+            best_pos = pos;
+            best_strand = strand;
+        }
+    }
+    Some((best_pos, best_strand))
+}
+
+/// Backing storage for [`SparseSuffixArray::sa`]: either a heap-allocated
+/// `Vec` (freshly built, or read via [`SIndex::load`]), or a slice borrowed
+/// from the mapping created by [`SIndex::load_mmap`] (kept alive alongside
+/// it in `SIndex::_mmap`), so the hot `sa_search` path reads straight from
+/// mapped pages instead of a heap copy. Mirrors `SuffixArray`'s own
+/// `SaStorage` in `indices::suffix_array`.
+enum SaStorage {
+    Owned(Vec<u32>),
+    Mapped(&'static [u32]),
+}
+
+impl SaStorage {
+    fn as_slice(&self) -> &[u32] {
+        match self {
+            SaStorage::Owned(v) => v,
+            SaStorage::Mapped(s) => s,
+        }
+    }
 }
 
 /// A 32-bit suffix array that owns the corresponding text.
-#[derive(MemSize, MemDbg)]
 pub struct SparseSuffixArray {
-    sa: Vec<u32>,
+    sa: SaStorage,
+}
+
+impl MemSize for SparseSuffixArray {
+    /// An `mmap`-backed `sa` doesn't count towards heap usage; see
+    /// [`SaStorage`].
+    fn mem_size(&self, flags: SizeFlags) -> usize {
+        match &self.sa {
+            SaStorage::Owned(v) => v.mem_size(flags),
+            SaStorage::Mapped(_) => 0,
+        }
+    }
 }
 
 impl SparseSuffixArray {
     pub fn new<'i>(text: impl Seq<'i>, mut indices: Vec<u32>) -> Self {
         let n = text.len();
         indices.sort_unstable_by_key(|idx| text.slice(*idx as usize..n));
-        Self { sa: indices }
+        Self { sa: SaStorage::Owned(indices) }
     }
 
     pub fn sa_size(&self) -> usize {
-        self.sa.mem_size(SizeFlags::default())
+        self.mem_size(SizeFlags::default())
     }
 
     pub fn log_sizes(&self, stats: &Stats) {
@@ -272,7 +1137,8 @@ impl SparseSuffixArray {
     /// Search text `t` for pattern `p` given (sparse) suffix array `sa`.
     /// Returns a `(pos, cnt)` pair where `pos` is the index of the first match and `cnt` is the number of matches.
     fn sa_search<'i, S: Seq<'i>>(&self, seq: S, p: S) -> (usize, usize) {
-        let mut size = self.sa.len();
+        let sa = self.sa.as_slice();
+        let mut size = sa.len();
         let mut half;
         let mut match_;
         let mut lmatch = 0;
@@ -287,7 +1153,7 @@ impl SparseSuffixArray {
         let mut r;
 
         if p.len() == 0 {
-            return (0, self.sa.len());
+            return (0, sa.len());
         }
 
         while size > 0 {
@@ -296,7 +1162,7 @@ impl SparseSuffixArray {
             r = self.compare(
                 seq,
                 p,
-                self.sa[i as usize + half as usize] as usize,
+                sa[i as usize + half as usize] as usize,
                 &mut match_,
             );
             if r.is_lt() {
@@ -320,7 +1186,7 @@ impl SparseSuffixArray {
                     r = self.compare(
                         seq,
                         p,
-                        self.sa[j as usize + half as usize] as usize,
+                        sa[j as usize + half as usize] as usize,
                         &mut lmatch,
                     );
                     if r.is_lt() {
@@ -342,7 +1208,7 @@ impl SparseSuffixArray {
                     r = self.compare(
                         seq,
                         p,
-                        self.sa[k as usize + half as usize] as usize,
+                        sa[k as usize + half as usize] as usize,
                         &mut rmatch,
                     );
                     if r.is_le() {
@@ -366,6 +1232,48 @@ impl SparseSuffixArray {
 
     fn query<'s, S: Seq<'s>>(&'s self, seq: S, pattern: S) -> impl Iterator<Item = usize> + 's {
         let (pos, cnt) = self.sa_search(seq, pattern);
-        (pos..pos + cnt).map(move |i| self.sa[i as usize] as usize)
+        let sa = self.sa.as_slice();
+        (pos..pos + cnt).map(move |i| sa[i as usize] as usize)
+    }
+
+    /// Append matching positions into `out` instead of allocating an iterator.
+    fn query_into<'s, S: Seq<'s>>(&'s self, seq: S, pattern: S, out: &mut Vec<usize>) -> usize {
+        let (pos, cnt) = self.sa_search(seq, pattern);
+        let sa = self.sa.as_slice();
+        out.extend((pos..pos + cnt).map(|i| sa[i as usize] as usize));
+        cnt
+    }
+
+    /// Write `sa` to `w` as a `u64` length, padded with zero bytes up to the
+    /// next 4-byte boundary, then raw little-endian `u32` entries — so that,
+    /// given `pos_before` (the file offset `w` is positioned at), the entry
+    /// data starts 4-byte aligned and [`SIndex::load_mmap`] can reinterpret
+    /// it in place as `&[u32]`.
+    fn write_sa(sa: &[u32], pos_before: usize, w: &mut dyn Write) -> io::Result<()> {
+        w.write_all(&(sa.len() as u64).to_le_bytes())?;
+        let pad = (4 - (pos_before + 8) % 4) % 4;
+        w.write_all(&vec![0u8; pad])?;
+        for &x in sa {
+            w.write_all(&x.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Read back an `sa` array written by [`Self::write_sa`]; `pos_before`
+    /// must match the value passed to `write_sa`.
+    fn read_sa(pos_before: usize, r: &mut dyn Read) -> io::Result<Vec<u32>> {
+        let mut len_buf = [0u8; 8];
+        r.read_exact(&mut len_buf)?;
+        let len = u64::from_le_bytes(len_buf) as usize;
+        let pad = (4 - (pos_before + 8) % 4) % 4;
+        let mut discard = vec![0u8; pad];
+        r.read_exact(&mut discard)?;
+        let mut sa = Vec::with_capacity(len);
+        let mut buf = [0u8; 4];
+        for _ in 0..len {
+            r.read_exact(&mut buf)?;
+            sa.push(u32::from_le_bytes(buf));
+        }
+        Ok(sa)
     }
 }
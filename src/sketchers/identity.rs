@@ -1,7 +1,17 @@
+use std::{
+    io::{self, Read, Write},
+    path::Path,
+};
+
 use packed_seq::PackedSeq;
 use serde_json::Value;
 
 use super::*;
+use crate::{FromReader, ToWriter};
+
+/// Identifies an `Identity` persisted file so loading a mismatched file
+/// fails cleanly instead of producing garbage.
+const MAGIC: &[u8; 8] = b"UIDXID1\0";
 
 /// 'Sketch' the packed sequence into an unpacked representation.
 /// Convenient for testing purposes.
@@ -32,6 +42,14 @@ impl SketcherBuilder for IdentityParams {
             MsSequence(seq),
         )
     }
+
+    /// Load an [`Identity::save`]d sketcher back from `path`.
+    fn load(&self, path: &Path) -> io::Result<Box<dyn Sketcher>> {
+        let mut r = io::BufReader::new(std::fs::File::open(path)?);
+        let mut sketcher = Identity::from_reader(&mut r)?;
+        sketcher.params = *self;
+        Ok(Box::new(sketcher))
+    }
 }
 
 impl Sketcher for Identity {
@@ -48,11 +66,18 @@ impl Sketcher for Identity {
     }
 
     fn sketch(&self, seq: PackedSeq) -> Result<(MsSequence, usize), SketchError> {
+        let (ms_sequence, _positions) = self.sketch_with_positions(seq)?;
+        Ok((ms_sequence, 0))
+    }
+
+    /// Every base is its own 'minimizer', so position `i` is simply `i`.
+    fn sketch_with_positions(&self, seq: PackedSeq) -> Result<(MsSequence, Vec<usize>), SketchError> {
         let seq = seq
             .iter_bp()
             .map(|x| x + (if self.params.skip_zero { 1 } else { 0 }))
             .collect::<Vec<_>>();
-        Ok((MsSequence(seq), 0))
+        let positions = (0..seq.len()).collect();
+        Ok((MsSequence(seq), positions))
     }
 
     fn ms_pos_to_plain_pos(&self, ms_pos: usize) -> Option<usize> {
@@ -66,4 +91,49 @@ impl Sketcher for Identity {
     fn get_ms_minimizer_via_plaintext(&self, seq: PackedSeq, ms_pos: usize) -> Option<usize> {
         Some(seq.get(ms_pos) as usize + (if self.params.skip_zero { 1 } else { 0 }))
     }
+
+    /// Write `params.skip_zero` and `len` to `path` behind a small header,
+    /// so [`IdentityParams::load`] can rebuild this sketcher without
+    /// re-scanning the input sequence.
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+        self.to_writer(&mut w)
+    }
+}
+
+impl ToWriter for Identity {
+    fn to_writer(&self, w: &mut dyn Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[self.params.skip_zero as u8])?;
+        w.write_all(&(self.len as u64).to_le_bytes())?;
+        Ok(())
+    }
+}
+
+impl FromReader for Identity {
+    /// Read an `Identity` previously written by [`ToWriter::to_writer`].
+    /// Callers going through [`IdentityParams::load`] overwrite `params`
+    /// right after with their own builder's params, matching the
+    /// [`super::minimizers::MinimizerSketcher`] convention.
+    fn from_reader(r: &mut dyn Read) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not an Identity file (magic mismatch)",
+            ));
+        }
+        let mut skip_zero = [0u8; 1];
+        r.read_exact(&mut skip_zero)?;
+        let mut len_buf = [0u8; 8];
+        r.read_exact(&mut len_buf)?;
+        let len = u64::from_le_bytes(len_buf) as usize;
+        Ok(Identity {
+            params: IdentityParams {
+                skip_zero: skip_zero[0] != 0,
+            },
+            len,
+        })
+    }
 }
@@ -1,4 +1,8 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    path::Path,
+};
 
 use cacheline_ef::CachelineEfVec;
 use itertools::Itertools;
@@ -10,9 +14,13 @@ use tracing::{info, trace};
 
 use crate::{
     utils::{Stats, Timer},
-    MsSequence, SketchError, Sketcher, SketcherBuilder,
+    FromReader, MsSequence, SketchError, Sketcher, SketcherBuilder, ToWriter,
 };
 
+/// Identifies a `MinimizerSketcher` file so loading a mismatched file fails
+/// cleanly instead of producing garbage.
+const MAGIC: &[u8; 8] = b"UIDXMM1\0";
+
 /// A packed minimizer representation.
 /// Bit width of the underlying alphabet is unspecified, and should not matter:
 /// really this should only be used as a unique identifier of the kmer/minimizer
@@ -171,6 +179,14 @@ impl<SV: SeqVec> SketcherBuilder<SV> for MinimizerParams {
             .expect("All minimizers of the input should be found");
         (Box::new(sketcher), ms_sequence)
     }
+
+    /// Load a [`MinimizerSketcher::save`]d sketcher back from `path`.
+    fn load(&self, path: &Path) -> io::Result<Box<dyn Sketcher<SV>>> {
+        let mut r = io::BufReader::new(std::fs::File::open(path)?);
+        let mut sketcher = MinimizerSketcher::from_reader(&mut r)?;
+        sketcher.params = *self;
+        Ok(Box::new(sketcher))
+    }
 }
 
 #[derive(MemSize)]
@@ -220,6 +236,10 @@ impl<SV: SeqVec> Sketcher<SV> for MinimizerSketcher {
         self.params.k
     }
 
+    fn l(&self) -> usize {
+        self.params.l
+    }
+
     /// Return the number of minimizers.
     fn len(&self) -> usize {
         match &self.min_poss {
@@ -229,13 +249,20 @@ impl<SV: SeqVec> Sketcher<SV> for MinimizerSketcher {
     }
 
     fn sketch(&self, seq: SV::Seq<'_>) -> Result<(MsSequence, usize), SketchError> {
+        let (ms_sequence, positions) = self.sketch_with_positions(seq)?;
+        let offset = *positions.first().ok_or(SketchError::TooShort)?;
+        Ok((ms_sequence, offset))
+    }
+
+    fn sketch_with_positions(&self, seq: SV::Seq<'_>) -> Result<(MsSequence, Vec<usize>), SketchError> {
         let (min_poss, min_vals): (Vec<Pos>, Vec<KmerVal>) = self.params.minimizers(seq).unzip();
-        let offset = *min_poss.first().ok_or(SketchError::TooShort)?;
-        Ok((
-            self.remap_minimizer_values(&min_vals)
-                .ok_or(SketchError::UnknownMinimizer)?,
-            offset,
-        ))
+        if min_poss.is_empty() {
+            return Err(SketchError::TooShort);
+        }
+        let ms_sequence = self
+            .remap_minimizer_values(&min_vals)
+            .ok_or(SketchError::UnknownMinimizer)?;
+        Ok((ms_sequence, min_poss))
     }
 
     fn ms_pos_to_plain_pos(&self, ms_pos: usize) -> Option<usize> {
@@ -267,4 +294,111 @@ impl<SV: SeqVec> Sketcher<SV> for MinimizerSketcher {
             Some(kmer as usize)
         }
     }
+
+    /// Write the kmer remapping and minimizer positions to `path` behind a
+    /// small header, so [`MinimizerParams::load`] can rebuild this sketcher
+    /// without re-scanning the input sequence for minimizers.
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+        self.to_writer(&mut w)?;
+        w.flush()
+    }
+}
+
+impl ToWriter for MinimizerSketcher {
+    fn to_writer(&self, w: &mut dyn Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        let is_cacheline_ef = matches!(self.min_poss, MinimizerPositions::CachelineEf(_));
+        w.write_all(&[is_cacheline_ef as u8])?;
+        w.write_all(&(self.kmer_width as u64).to_le_bytes())?;
+
+        w.write_all(&(self.kmer_map.len() as u64).to_le_bytes())?;
+        for (&kmer, &id) in &self.kmer_map {
+            w.write_all(&kmer.to_le_bytes())?;
+            w.write_all(&(id as u64).to_le_bytes())?;
+        }
+
+        let positions = match &self.min_poss {
+            MinimizerPositions::EliasFano(ef) => {
+                (0..ef.len()).map(|i| ef.get(i) as u64).collect_vec()
+            }
+            MinimizerPositions::CachelineEf(cl) => (0..cl.len()).map(|i| cl.index(i)).collect_vec(),
+        };
+        w.write_all(&(positions.len() as u64).to_le_bytes())?;
+        for p in &positions {
+            w.write_all(&p.to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+impl FromReader for MinimizerSketcher {
+    /// Read a sketcher previously written by [`ToWriter::to_writer`]. The
+    /// resulting `params` has a dummy `k`/`l`/`skip_zero` (`remap` is inferred
+    /// from whether a kmer map was saved); callers going through
+    /// [`MinimizerParams::load`] overwrite it with their own builder's params
+    /// right after, since `k`/`l`/`skip_zero` aren't recoverable from the
+    /// minimizer-space bytes alone.
+    fn from_reader(r: &mut dyn Read) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a MinimizerSketcher file (magic mismatch)",
+            ));
+        }
+
+        let mut flag = [0u8; 1];
+        r.read_exact(&mut flag)?;
+        let cacheline_ef = flag[0] != 0;
+
+        let mut buf8 = [0u8; 8];
+        r.read_exact(&mut buf8)?;
+        let kmer_width = u64::from_le_bytes(buf8) as usize;
+
+        r.read_exact(&mut buf8)?;
+        let kmer_map_len = u64::from_le_bytes(buf8) as usize;
+        let mut kmer_map = HashMap::with_capacity(kmer_map_len);
+        for _ in 0..kmer_map_len {
+            r.read_exact(&mut buf8)?;
+            let kmer = KmerVal::from_le_bytes(buf8);
+            r.read_exact(&mut buf8)?;
+            kmer_map.insert(kmer, u64::from_le_bytes(buf8) as usize);
+        }
+
+        r.read_exact(&mut buf8)?;
+        let num_positions = u64::from_le_bytes(buf8) as usize;
+        let mut positions = Vec::with_capacity(num_positions);
+        for _ in 0..num_positions {
+            r.read_exact(&mut buf8)?;
+            positions.push(u64::from_le_bytes(buf8));
+        }
+
+        let min_poss = if cacheline_ef {
+            MinimizerPositions::CachelineEf(CachelineEfVec::new(&positions))
+        } else {
+            let mut builder = sux::dict::elias_fano::EliasFanoBuilder::new(
+                positions.len(),
+                *positions.last().unwrap_or(&0) as usize,
+            );
+            for &p in &positions {
+                builder.push(p as usize);
+            }
+            MinimizerPositions::EliasFano(builder.build_with_seq())
+        };
+
+        Ok(MinimizerSketcher {
+            params: MinimizerParams {
+                k: 0,
+                l: 0,
+                remap: !kmer_map.is_empty(),
+                cacheline_ef,
+                skip_zero: false,
+            },
+            min_poss,
+            kmer_map,
+            kmer_width,
+        })
+    }
 }
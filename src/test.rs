@@ -276,6 +276,70 @@ fn test_s_index() {
     }
 }
 
+#[test]
+fn test_uindex_save_load_roundtrip() {
+    let seq = PackedSeqVec::random(100000);
+    let sketcher = &MinimizerParams {
+        l: 20,
+        k: 8,
+        remap: true,
+        cacheline_ef: false,
+        skip_zero: false,
+    };
+    let ms_index = &LibSaisSa {
+        store_ms_seq: true,
+        par: false,
+    };
+    let uindex = UIndex::build(&seq, sketcher, ms_index);
+
+    let dir = tempfile::tempdir().unwrap();
+    uindex.save(dir.path()).unwrap();
+    let loaded = UIndex::load(dir.path(), &seq, sketcher, ms_index).unwrap();
+    let loaded_mmap = UIndex::load_mmap(dir.path(), &seq, sketcher, ms_index).unwrap();
+
+    for _ in 0..100 {
+        let len: usize = rand::random_range(20..120);
+        let pos = rand::random_range(..seq.len() - len);
+        let query = seq.slice(pos..pos + len);
+
+        let mut occ = uindex.query(query).unwrap().collect::<Vec<_>>();
+        let mut loaded_occ = loaded.query(query).unwrap().collect::<Vec<_>>();
+        let mut loaded_mmap_occ = loaded_mmap.query(query).unwrap().collect::<Vec<_>>();
+        occ.sort();
+        loaded_occ.sort();
+        loaded_mmap_occ.sort();
+        assert_eq!(occ, loaded_occ);
+        assert_eq!(occ, loaded_mmap_occ);
+    }
+}
+
+#[test]
+fn test_s_index_save_load_roundtrip() {
+    let seq = PackedSeqVec::random(100000);
+    let sindex = SIndex::build(&seq, 8, 20);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("sindex.bin");
+    sindex.save(&path).unwrap();
+    let loaded = SIndex::<PackedSeqVec>::load(&path).unwrap();
+    let loaded_mmap = SIndex::<PackedSeqVec>::load_mmap(&path).unwrap();
+
+    for _ in 0..100 {
+        let len = 20 + rand::random_range(..100usize);
+        let pos = rand::random_range(..seq.len() - len);
+        let query = seq.slice(pos..pos + len);
+
+        let mut occ = sindex.query(query).unwrap().collect::<Vec<_>>();
+        let mut loaded_occ = loaded.query(query).unwrap().collect::<Vec<_>>();
+        let mut loaded_mmap_occ = loaded_mmap.query(query).unwrap().collect::<Vec<_>>();
+        occ.sort();
+        loaded_occ.sort();
+        loaded_mmap_occ.sort();
+        assert_eq!(occ, loaded_occ);
+        assert_eq!(occ, loaded_mmap_occ);
+    }
+}
+
 #[test]
 #[ignore = "needs human-genome.fa"]
 fn human_genome() {
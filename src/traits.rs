@@ -1,8 +1,32 @@
+use std::{
+    io::{self, Read, Write},
+    path::Path,
+};
+
 use mem_dbg::MemSize;
 use packed_seq::*;
 
 use crate::{utils::Stats, MsSequence};
 
+fn unsupported(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, format!("{what} is not implemented for this type"))
+}
+
+/// Read `Self` back from a byte stream previously written by [`ToWriter`].
+/// Mirrors `decomp-toolkit`'s switch away from `binrw`/`byteorder` to plain
+/// reader/writer traits: each implementor owns its exact on-disk layout
+/// (typically a small header followed by raw little-endian fields), instead
+/// of relying on derive-macro magic.
+pub trait FromReader: Sized {
+    fn from_reader(r: &mut dyn Read) -> io::Result<Self>;
+}
+
+/// Write `Self` to a byte stream that [`FromReader::from_reader`] can later
+/// reconstruct it from.
+pub trait ToWriter {
+    fn to_writer(&self, w: &mut dyn Write) -> io::Result<()>;
+}
+
 /// A generic index to locate strings.
 /// The index owns the input text.
 pub trait IndexBuilder<SV: SeqVec>: std::fmt::Debug {
@@ -26,10 +50,30 @@ pub trait IndexBuilder<SV: SeqVec>: std::fmt::Debug {
     fn build(&self, text: Vec<u8>, width: usize) -> Box<dyn Index<SV>> {
         self.build_with_stats(text, width, &Stats::default())
     }
+
+    /// Load a previously-[`Index::save`]d index of this builder's kind back
+    /// from `path`, instead of rebuilding it from scratch. Returns an error
+    /// when this builder does not (yet) support persistence.
+    fn load(&self, _path: &Path, _width: usize) -> io::Result<Box<dyn Index<SV>>> {
+        Err(unsupported("IndexBuilder::load"))
+    }
+
+    /// Like [`Self::load`], but memory-maps the file read-only instead of
+    /// copying it into the heap, so e.g. a large suffix array can be
+    /// reloaded without its resident pages showing up as freshly-allocated
+    /// in `max_rss()`. Returns an error when this builder does not (yet)
+    /// support mmap-backed loading.
+    fn load_mmap(&self, _path: &Path, _width: usize) -> io::Result<Box<dyn Index<SV>>> {
+        Err(unsupported("IndexBuilder::load_mmap"))
+    }
 }
 
 // FIXME: Re-add MemDbg super trait.
-pub trait Index<SV: SeqVec>: MemSize {
+/// `Send + Sync` so that a built `Box<dyn Index<SV>>` can be shared across
+/// threads for parallel querying (see `UIndex::bench_parallel`) — an index
+/// is read-only after construction, so this is always safe for a correct
+/// implementor.
+pub trait Index<SV: SeqVec>: MemSize + Send + Sync {
     /// Return all places where the pattern occurs.
     fn query(
         &self,
@@ -37,6 +81,54 @@ pub trait Index<SV: SeqVec>: MemSize {
         seq: SV::Seq<'_>,
         sketcher: &dyn Sketcher<SV>,
     ) -> Box<dyn Iterator<Item = usize> + '_>;
+
+    /// Append all places where the pattern occurs to `out`, and return how
+    /// many positions were appended.
+    ///
+    /// This avoids the per-query allocation of boxing an iterator (and, for
+    /// implementations that materialize their matches anyway, of a
+    /// throwaway `Vec`) by reusing a caller-owned buffer across many calls,
+    /// as done by the bench loops that issue millions of queries.
+    fn query_into(
+        &self,
+        pattern: &[u8],
+        seq: SV::Seq<'_>,
+        sketcher: &dyn Sketcher<SV>,
+        out: &mut Vec<usize>,
+    ) -> usize {
+        let start = out.len();
+        out.extend(self.query(pattern, seq, sketcher));
+        out.len() - start
+    }
+
+    /// Return the number of places where the pattern occurs, without
+    /// materializing them.
+    ///
+    /// The default falls back to draining [`Self::query`], so every
+    /// implementor is still correct out of the box; override this when the
+    /// underlying index can answer a count without enumerating matches
+    /// (e.g. the `(pos, cnt)` a suffix-array binary search already
+    /// produces), turning abundance/occurrence-style queries into O(log n)
+    /// instead of O(log n + occ).
+    fn count(&self, pattern: &[u8], seq: SV::Seq<'_>, sketcher: &dyn Sketcher<SV>) -> usize {
+        self.query(pattern, seq, sketcher).count()
+    }
+
+    /// Persist this index to `path` so it can later be rebuilt cheaply via
+    /// the matching [`IndexBuilder::load`], instead of being rebuilt from
+    /// the input text. Returns an error when this index does not (yet)
+    /// support persistence.
+    fn save(&self, _path: &Path) -> io::Result<()> {
+        Err(unsupported("Index::save"))
+    }
+
+    /// `std::any::type_name` of the concrete implementor, recorded in a
+    /// saved [`crate::UIndex`]'s manifest so a later `load`/`load_mmap` can
+    /// reject an `index_params` of the wrong kind with a clear error,
+    /// instead of silently reconstructing garbage from mismatched bytes.
+    fn type_tag(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
 }
 
 /// Sketch a plain sequence to minimizer space.
@@ -53,6 +145,13 @@ pub trait SketcherBuilder<SV: SeqVec>: std::fmt::Debug {
     fn sketch(&self, seq: SV::Seq<'_>) -> (Box<dyn Sketcher<SV>>, MsSequence) {
         self.sketch_with_stats(seq, &Stats::default())
     }
+
+    /// Load a previously-[`Sketcher::save`]d sketcher of this builder's kind
+    /// back from `path`. Returns an error when this builder does not (yet)
+    /// support persistence.
+    fn load(&self, _path: &Path) -> io::Result<Box<dyn Sketcher<SV>>> {
+        Err(unsupported("SketcherBuilder::load"))
+    }
 }
 
 pub enum SketchError {
@@ -63,12 +162,21 @@ pub enum SketchError {
     UnknownMinimizer,
 }
 
-pub trait Sketcher<SV: SeqVec>: MemSize {
+/// `Send + Sync` for the same reason as [`Index`]: a built sketcher is
+/// read-only, so sharing it across threads for parallel querying is safe.
+pub trait Sketcher<SV: SeqVec>: MemSize + Send + Sync {
     /// Returns the width in bytes of each minimizer.
     fn width(&self) -> usize;
 
     fn k(&self) -> usize;
 
+    /// The minimizer window length: every `l` consecutive plain-sequence
+    /// bases are guaranteed to contain at least one minimizer. Defaults to
+    /// `k()` for sketchers without a windowing scheme of their own.
+    fn l(&self) -> usize {
+        self.k()
+    }
+
     fn len(&self) -> usize;
 
     /// Take an input text, compute its minimizers, and compress those into the
@@ -79,6 +187,18 @@ pub trait Sketcher<SV: SeqVec>: MemSize {
     /// Returns `None` when `seq` is too short to contain a minimizer.
     fn sketch(&self, seq: SV::Seq<'_>) -> Result<(MsSequence, usize), SketchError>;
 
+    /// Like [`Self::sketch`], but returns the plain-sequence position of
+    /// *every* minimizer in the returned [`MsSequence`] (one per
+    /// `self.width()`-byte entry, in order), not just the first.
+    ///
+    /// [`crate::UIndex::query_from_reader`] uses this to sketch a query
+    /// incrementally as bytes arrive: a minimizer whose window lies
+    /// entirely before the tail of the currently-buffered bytes can't
+    /// change as more data streams in, so it can be emitted immediately;
+    /// knowing only the first minimizer's position (as [`Self::sketch`]
+    /// does) isn't enough to tell which ones those are.
+    fn sketch_with_positions(&self, seq: SV::Seq<'_>) -> Result<(MsSequence, Vec<usize>), SketchError>;
+
     /// Take a *byte* position of a character in the minimizer space, and return its start position in the original sequence.
     /// Returns `None` when the position in the minimizer space text is not aligned with the size of the encoded minimizers.
     fn ms_pos_to_plain_pos(&self, ms_pos: usize) -> Option<usize>;
@@ -88,4 +208,19 @@ pub trait Sketcher<SV: SeqVec>: MemSize {
 
     /// Return the value of the minimizer at the given position in the sketched sequence.
     fn get_ms_minimizer_via_plaintext(&self, seq: SV::Seq<'_>, ms_pos: usize) -> Option<usize>;
+
+    /// Persist this sketcher's parameters and derived state to `path` so it
+    /// can later be rebuilt cheaply via the matching [`SketcherBuilder::load`].
+    /// Returns an error when this sketcher does not (yet) support persistence.
+    fn save(&self, _path: &Path) -> io::Result<()> {
+        Err(unsupported("Sketcher::save"))
+    }
+
+    /// `std::any::type_name` of the concrete implementor, recorded in a
+    /// saved [`crate::UIndex`]'s manifest so a later `load`/`load_mmap` can
+    /// reject a `sketch_params` of the wrong kind with a clear error,
+    /// instead of silently reconstructing garbage from mismatched bytes.
+    fn type_tag(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
 }
@@ -1,13 +1,29 @@
-use std::{cell::RefCell, collections::HashMap, ops::Range};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{self, Read, Write},
+    ops::Range,
+    path::Path,
+};
 
 use mem_dbg::{MemDbg, MemSize, SizeFlags};
 use packed_seq::*;
+use rayon::prelude::*;
 use serde_json::Value;
 use sux::traits::SuccUnchecked;
 use tracing::{info, trace};
 
 use crate::{traits::*, utils::*};
 
+/// Identifies a [`UIndex::save`]d directory, so loading a mismatched or
+/// unrelated directory fails cleanly instead of producing garbage.
+const MANIFEST_MAGIC: &[u8; 8] = b"UIDXMF1\0";
+/// Bumped whenever the manifest/directory layout changes incompatibly.
+/// v2 added the sketcher/index `type_tag`s so a mismatched `sketch_params`/
+/// `index_params` at load time is rejected with a clear error instead of
+/// producing garbage.
+const MANIFEST_VERSION: u32 = 2;
+
 #[derive(MemSize)]
 pub struct UIndex<'s, SV: SeqVec> {
     pub(crate) seq: &'s SV,
@@ -18,6 +34,150 @@ pub struct UIndex<'s, SV: SeqVec> {
     ranges: sux::dict::elias_fano::EfDict,
 }
 
+/// Which strand of the input a [`UIndex::query_both_strands`] match was
+/// found on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strand {
+    Forward,
+    Reverse,
+}
+
+/// Aggregate throughput and latency produced by [`UIndex::bench_parallel`].
+#[derive(Debug, Clone, Copy)]
+pub struct QueryThroughput {
+    pub queries_per_sec: f64,
+    pub p50_latency: std::time::Duration,
+    pub p99_latency: std::time::Duration,
+}
+
+impl QueryThroughput {
+    pub(crate) fn from_latencies(
+        num_queries: usize,
+        elapsed: std::time::Duration,
+        mut latencies: Vec<std::time::Duration>,
+    ) -> Self {
+        latencies.sort_unstable();
+        let percentile = |p: f64| {
+            latencies
+                .get((((latencies.len().max(1) - 1) as f64) * p).round() as usize)
+                .copied()
+                .unwrap_or_default()
+        };
+        Self {
+            queries_per_sec: num_queries as f64 / elapsed.as_secs_f64(),
+            p50_latency: percentile(0.50),
+            p99_latency: percentile(0.99),
+        }
+    }
+}
+
+/// The part of [`UIndex::query_into`] that doesn't touch `query_stats`,
+/// factored out so [`UIndex::bench_parallel`] can call it with borrowed
+/// `sketcher`/`ms_index`/`ranges` instead of `&self` — `Index`/`Sketcher`
+/// are `Send + Sync`, but `UIndex` itself isn't (its `query_stats` is a
+/// `RefCell`), so sharing `&self` across threads isn't an option.
+fn query_match_count<SV: SeqVec>(
+    sketcher: &dyn Sketcher<SV>,
+    ms_index: &dyn Index<SV>,
+    ranges: &sux::dict::elias_fano::EfDict,
+    seq: &SV,
+    pattern: SV::Seq<'_>,
+) -> usize {
+    let Ok((ms_pattern, offset)) = sketcher.sketch(pattern) else {
+        return 0;
+    };
+    let mut ms_occ = Vec::new();
+    ms_index.query_into(&ms_pattern.0, seq.as_slice(), sketcher, &mut ms_occ);
+
+    let mut count = 0;
+    for ms_pos in ms_occ {
+        let Some(plain_pos) = sketcher.ms_pos_to_plain_pos(ms_pos) else {
+            continue;
+        };
+        let Some(start) = plain_pos.checked_sub(offset) else {
+            continue;
+        };
+        let end = start + pattern.len();
+        if end > seq.len() {
+            continue;
+        }
+        if seq.slice(start..end) != pattern {
+            continue;
+        }
+        let range_end = unsafe { ranges.succ_unchecked::<true>(start).1 };
+        if end > range_end {
+            continue;
+        }
+        count += 1;
+    }
+    count
+}
+
+/// Like [`query_match_count`], but returns every match position (not just
+/// the count) together with a freshly computed [`QueryStats`] delta,
+/// instead of touching a shared counter. Used by [`UIndex::query_batch`]
+/// so each pattern answered on a rayon worker thread accumulates its own
+/// stats locally; the caller merges all of them into the shared
+/// `query_stats` once the parallel portion has finished, instead of every
+/// worker contending on its `RefCell`.
+fn query_positions_with_stats<SV: SeqVec>(
+    sketcher: &dyn Sketcher<SV>,
+    ms_index: &dyn Index<SV>,
+    ranges: &sux::dict::elias_fano::EfDict,
+    seq: &SV,
+    pattern: SV::Seq<'_>,
+) -> (Vec<usize>, QueryStats) {
+    let mut stats = QueryStats {
+        queries: 1,
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+
+    let (ms_pattern, offset) = match sketcher.sketch(pattern) {
+        Ok(x) => x,
+        Err(SketchError::TooShort) => {
+            stats.too_short += 1;
+            return (out, stats);
+        }
+        Err(SketchError::UnknownMinimizer) => {
+            stats.unknown_minimizer += 1;
+            return (out, stats);
+        }
+    };
+
+    let mut ms_occ = Vec::new();
+    ms_index.query_into(&ms_pattern.0, seq.as_slice(), sketcher, &mut ms_occ);
+
+    for ms_pos in ms_occ {
+        let Some(plain_pos) = sketcher.ms_pos_to_plain_pos(ms_pos) else {
+            stats.misaligned_ms_pos += 1;
+            continue;
+        };
+        let Some(start) = plain_pos.checked_sub(offset) else {
+            stats.out_of_bounds += 1;
+            continue;
+        };
+        let end = start + pattern.len();
+        if end > seq.len() {
+            stats.out_of_bounds += 1;
+            continue;
+        }
+        if seq.slice(start..end) != pattern {
+            stats.mismatches += 1;
+            continue;
+        }
+        let range_end = unsafe { ranges.succ_unchecked::<true>(start).1 };
+        if end > range_end {
+            stats.bad_ranges += 1;
+            continue;
+        }
+        stats.matches += 1;
+        stats.forward_matches += 1;
+        out.push(start);
+    }
+    (out, stats)
+}
+
 #[derive(MemSize, MemDbg, Default, Debug)]
 pub struct QueryStats {
     /// The total number of queries.
@@ -36,6 +196,19 @@ pub struct QueryStats {
     pub bad_ranges: usize,
     /// Matches.
     pub matches: usize,
+    /// Of `matches`, how many were found on the forward strand (always all
+    /// of them for [`UIndex::query`]/[`UIndex::query_into`]; see
+    /// [`UIndex::query_both_strands`]).
+    pub forward_matches: usize,
+    /// Of `matches`, how many were found on the reverse-complement strand
+    /// (see [`UIndex::query_both_strands`]).
+    pub reverse_matches: usize,
+    /// Number of seed hits Hamming-verified in sequence space by
+    /// [`UIndex::query_approx_into`] (deduplicated across seeds).
+    pub approx_candidates: usize,
+    /// Of the matches accepted by [`UIndex::query_approx_into`], how many
+    /// had each mismatch count, keyed by mismatch count.
+    pub approx_mismatch_histogram: HashMap<u32, usize>,
 
     /// Total time in ns of sketching queries.
     pub t_sketch: usize,
@@ -49,6 +222,34 @@ pub struct QueryStats {
     pub t_ranges: usize,
 }
 
+impl QueryStats {
+    /// Add `other`'s counters into `self`. Used by [`UIndex::query_batch`]
+    /// to combine the per-thread accumulators produced by
+    /// [`query_positions_with_stats`] into the shared `query_stats` after
+    /// the parallel portion of a batch has finished.
+    fn merge(&mut self, other: &QueryStats) {
+        self.queries += other.queries;
+        self.too_short += other.too_short;
+        self.unknown_minimizer += other.unknown_minimizer;
+        self.misaligned_ms_pos += other.misaligned_ms_pos;
+        self.out_of_bounds += other.out_of_bounds;
+        self.mismatches += other.mismatches;
+        self.bad_ranges += other.bad_ranges;
+        self.matches += other.matches;
+        self.forward_matches += other.forward_matches;
+        self.reverse_matches += other.reverse_matches;
+        self.approx_candidates += other.approx_candidates;
+        for (&mismatches, &count) in &other.approx_mismatch_histogram {
+            *self.approx_mismatch_histogram.entry(mismatches).or_insert(0) += count;
+        }
+        self.t_sketch += other.t_sketch;
+        self.t_search += other.t_search;
+        self.t_invert_pos += other.t_invert_pos;
+        self.t_check += other.t_check;
+        self.t_ranges += other.t_ranges;
+    }
+}
+
 impl<'s, SV: SeqVec> Drop for UIndex<'s, SV> {
     fn drop(&mut self) {
         let QueryStats {
@@ -60,6 +261,10 @@ impl<'s, SV: SeqVec> Drop for UIndex<'s, SV> {
             mismatches,
             bad_ranges,
             matches,
+            forward_matches,
+            reverse_matches,
+            approx_candidates,
+            approx_mismatch_histogram,
             mut t_sketch,
             mut t_search,
             mut t_invert_pos,
@@ -87,6 +292,10 @@ out of bounds     {out_of_bounds:>9}
 mismatches        {mismatches:>9}
 bad_ranges        {bad_ranges:>9}
 matches           {matches:>9}
+forward matches   {forward_matches:>9}
+reverse matches   {reverse_matches:>9}
+approx candidates {approx_candidates:>9}
+approx mismatch histogram {approx_mismatch_histogram:?}
 t_sketch          {t_sketch:>9} ns/query
 t_search          {t_search:>9} ns/query
 t_invert_pos      {t_invert_pos:>9} ns/query
@@ -186,6 +395,7 @@ impl<'s, SV: SeqVec + 'static> UIndex<'s, SV> {
         stats.set("query_mismatches", qs.mismatches);
         stats.set("query_bad_ranges", qs.bad_ranges);
         stats.set("query_matches", qs.matches);
+        stats.set("query_approx_candidates", qs.approx_candidates);
         stats.set("t_query_sketch", qs.t_sketch as f32 / 1_000_000_000.);
         stats.set("t_query_search", qs.t_search as f32 / 1_000_000_000.);
         stats.set(
@@ -280,8 +490,860 @@ impl<'s, SV: SeqVec + 'static> UIndex<'s, SV> {
                 return None;
             }
 
-            self.query_stats.borrow_mut().matches += 1;
+            let mut stats = self.query_stats.borrow_mut();
+            stats.matches += 1;
+            stats.forward_matches += 1;
             Some(start)
         })))
     }
+
+    /// Like [`Self::query`], but appends match positions to a caller-owned
+    /// `out` buffer instead of allocating a boxed iterator, so a single
+    /// `Vec` can be reused across many queries. Returns the number of
+    /// matches appended, or `None` if the pattern is too short to sketch.
+    pub fn query_into<'p>(&'p self, pattern: SV::Seq<'p>, out: &mut Vec<usize>) -> Option<usize> {
+        self.query_stats.borrow_mut().queries += 1;
+        let t1 = std::time::Instant::now();
+        let (ms_pattern, offset) = match self.sketcher.sketch(pattern) {
+            Ok(x) => x,
+            Err(SketchError::TooShort) => {
+                self.query_stats.borrow_mut().too_short += 1;
+                return None;
+            }
+            Err(SketchError::UnknownMinimizer) => {
+                self.query_stats.borrow_mut().unknown_minimizer += 1;
+                return Some(0);
+            }
+        };
+        let t2 = std::time::Instant::now();
+        self.query_stats.borrow_mut().t_sketch += t2.duration_since(t1).subsec_nanos() as usize;
+
+        let mut ms_occ = Vec::new();
+        self.ms_index.query_into(
+            &ms_pattern.0,
+            self.seq.as_slice(),
+            &*self.sketcher,
+            &mut ms_occ,
+        );
+        let t3 = std::time::Instant::now();
+        self.query_stats.borrow_mut().t_search += t3.duration_since(t2).subsec_nanos() as usize;
+
+        let start_len = out.len();
+        for ms_pos in ms_occ {
+            let Some(plain_pos) = self.sketcher.ms_pos_to_plain_pos(ms_pos) else {
+                self.query_stats.borrow_mut().misaligned_ms_pos += 1;
+                continue;
+            };
+            let Some(start) = plain_pos.checked_sub(offset) else {
+                self.query_stats.borrow_mut().out_of_bounds += 1;
+                continue;
+            };
+            let end = start + pattern.len();
+            if end > self.seq.len() {
+                self.query_stats.borrow_mut().out_of_bounds += 1;
+                continue;
+            }
+            if self.seq.slice(start..end) != pattern {
+                self.query_stats.borrow_mut().mismatches += 1;
+                continue;
+            }
+            let range_end = unsafe { self.ranges.succ_unchecked::<true>(start).1 };
+            if end > range_end {
+                self.query_stats.borrow_mut().bad_ranges += 1;
+                continue;
+            }
+            let mut stats = self.query_stats.borrow_mut();
+            stats.matches += 1;
+            stats.forward_matches += 1;
+            drop(stats);
+            out.push(start);
+        }
+        Some(out.len() - start_len)
+    }
+
+    /// Like [`Self::query`], but returns only the number of verified
+    /// matches instead of collecting them, avoiding the caller needing a
+    /// `Vec` just to answer "how many times does `pattern` occur?".
+    pub fn query_count(&self, pattern: SV::Seq<'_>) -> usize {
+        self.query(pattern).map_or(0, Iterator::count)
+    }
+
+    /// Like [`Self::query_count`], but stops as soon as a single verified
+    /// match is found instead of counting every occurrence. `self.query`'s
+    /// returned iterator is lazy, so `next()` only sketches/searches as far
+    /// as needed and skips verifying (and timing) any remaining
+    /// minimizer-space occurrence once the first one survives the
+    /// sequence-space and range checks.
+    pub fn query_exists(&self, pattern: SV::Seq<'_>) -> bool {
+        self.query(pattern).is_some_and(|mut it| it.next().is_some())
+    }
+
+    /// The strand-tagged core of [`Self::query_both_strands`]: sketch
+    /// `verify_pattern` (already reverse-complemented in sequence space when
+    /// searching the reverse strand), search it in minimizer space, and
+    /// verify/push matches tagged with `strand`.
+    fn query_strand_into(
+        &self,
+        verify_pattern: SV::Seq<'_>,
+        strand: Strand,
+        out: &mut Vec<(usize, Strand)>,
+    ) -> Option<usize> {
+        self.query_stats.borrow_mut().queries += 1;
+        let (ms_pattern, offset) = match self.sketcher.sketch(verify_pattern) {
+            Ok(x) => x,
+            Err(SketchError::TooShort) => {
+                self.query_stats.borrow_mut().too_short += 1;
+                return None;
+            }
+            Err(SketchError::UnknownMinimizer) => {
+                self.query_stats.borrow_mut().unknown_minimizer += 1;
+                return Some(0);
+            }
+        };
+
+        let mut ms_occ = Vec::new();
+        self.ms_index.query_into(
+            &ms_pattern.0,
+            self.seq.as_slice(),
+            &*self.sketcher,
+            &mut ms_occ,
+        );
+
+        let start_len = out.len();
+        for ms_pos in ms_occ {
+            let Some(plain_pos) = self.sketcher.ms_pos_to_plain_pos(ms_pos) else {
+                self.query_stats.borrow_mut().misaligned_ms_pos += 1;
+                continue;
+            };
+            let Some(start) = plain_pos.checked_sub(offset) else {
+                self.query_stats.borrow_mut().out_of_bounds += 1;
+                continue;
+            };
+            let end = start + verify_pattern.len();
+            if end > self.seq.len() {
+                self.query_stats.borrow_mut().out_of_bounds += 1;
+                continue;
+            }
+            if self.seq.slice(start..end) != verify_pattern {
+                self.query_stats.borrow_mut().mismatches += 1;
+                continue;
+            }
+            let range_end = unsafe { self.ranges.succ_unchecked::<true>(start).1 };
+            if end > range_end {
+                self.query_stats.borrow_mut().bad_ranges += 1;
+                continue;
+            }
+            let mut stats = self.query_stats.borrow_mut();
+            stats.matches += 1;
+            match strand {
+                Strand::Forward => stats.forward_matches += 1,
+                Strand::Reverse => stats.reverse_matches += 1,
+            }
+            drop(stats);
+            out.push((start, strand));
+        }
+        Some(out.len() - start_len)
+    }
+
+    /// Like [`Self::query_into`], but also searches the reverse-complement
+    /// strand, tagging each match with the [`Strand`] it was found on.
+    /// Genomic queries almost always need both: a read can sequence either
+    /// strand of the double helix, so a pattern absent from the forward
+    /// strand may still be present as the reverse complement of a real hit.
+    ///
+    /// The minimizer-space index only ever holds forward-strand minimizers
+    /// of `self.seq`, so a reverse-strand hit can't be found by sketching
+    /// `pattern` as-is and reinterpreting the result: `pattern` is first
+    /// reverse-complemented in *sequence* space, so its minimizers (and thus
+    /// its minimizer-space seed) line up with what was actually indexed, and
+    /// the final `seq[start..end] == pattern` check is done against that
+    /// same reverse-complemented pattern, not the original.
+    pub fn query_both_strands(
+        &self,
+        pattern: SV::Seq<'_>,
+        out: &mut Vec<(usize, Strand)>,
+    ) -> Option<usize> {
+        let start_len = out.len();
+        self.query_strand_into(pattern, Strand::Forward, out)?;
+        let rc: SV = pattern.revcomp();
+        self.query_strand_into(rc.as_slice(), Strand::Reverse, out);
+        Some(out.len() - start_len)
+    }
+
+    /// Like [`Self::query_into`], but tolerant of up to `max_mismatches`
+    /// substitutions (Hamming distance) anywhere in `pattern`, instead of
+    /// requiring an exact match. Matches are tagged with their mismatch
+    /// count. Returns the number of matches appended, or `None` if
+    /// `pattern` is too short for even one seed (see below) to contain a
+    /// minimizer.
+    ///
+    /// A single wrong minimizer anywhere in `pattern` turns `query`'s
+    /// minimizer-space search into a miss (`UnknownMinimizer`, or a failed
+    /// `seq[start..end] == pattern` check), so it can't tolerate errors.
+    /// Seed-and-extend instead: split `pattern` into `max_mismatches + 1`
+    /// contiguous chunks and search each one *exactly* in minimizer space.
+    /// By the pigeonhole principle, whenever `pattern` truly occurs with at
+    /// most `max_mismatches` substitutions, at least one chunk is free of an
+    /// erroneous minimizer, so every real occurrence is seeded by at least
+    /// one chunk's exact hit. Each seed hit is then extended: mapped back to
+    /// a candidate window of `self.seq` and checked base-by-base, aborting
+    /// as soon as the running mismatch count exceeds `max_mismatches`.
+    /// Candidates reached by more than one seed are only verified once.
+    pub fn query_approx_into(
+        &self,
+        pattern: SV::Seq<'_>,
+        max_mismatches: u32,
+        out: &mut Vec<(usize, u32)>,
+    ) -> Option<usize> {
+        self.query_stats.borrow_mut().queries += 1;
+
+        let num_chunks = max_mismatches as usize + 1;
+        let chunk_len = pattern.len().div_ceil(num_chunks).max(1);
+
+        let mut seen: HashMap<usize, u32> = HashMap::new();
+        let mut verified: std::collections::HashSet<usize> = std::collections::HashSet::new();
+        let mut any_chunk_sketched = false;
+
+        let mut chunk_start = 0;
+        while chunk_start < pattern.len() {
+            let chunk_end = (chunk_start + chunk_len).min(pattern.len());
+            let chunk = pattern.slice(chunk_start..chunk_end);
+
+            let (ms_chunk, chunk_offset) = match self.sketcher.sketch(chunk) {
+                Ok(x) => x,
+                Err(SketchError::TooShort) => {
+                    self.query_stats.borrow_mut().too_short += 1;
+                    chunk_start += chunk_len;
+                    continue;
+                }
+                Err(SketchError::UnknownMinimizer) => {
+                    self.query_stats.borrow_mut().unknown_minimizer += 1;
+                    any_chunk_sketched = true;
+                    chunk_start += chunk_len;
+                    continue;
+                }
+            };
+            any_chunk_sketched = true;
+
+            let mut ms_occ = Vec::new();
+            self.ms_index.query_into(
+                &ms_chunk.0,
+                self.seq.as_slice(),
+                &*self.sketcher,
+                &mut ms_occ,
+            );
+
+            for ms_pos in ms_occ {
+                let Some(plain_pos) = self.sketcher.ms_pos_to_plain_pos(ms_pos) else {
+                    self.query_stats.borrow_mut().misaligned_ms_pos += 1;
+                    continue;
+                };
+                let Some(start) = plain_pos
+                    .checked_sub(chunk_offset)
+                    .and_then(|x| x.checked_sub(chunk_start))
+                else {
+                    self.query_stats.borrow_mut().out_of_bounds += 1;
+                    continue;
+                };
+                let end = start + pattern.len();
+                if end > self.seq.len() {
+                    self.query_stats.borrow_mut().out_of_bounds += 1;
+                    continue;
+                }
+                let range_end = unsafe { self.ranges.succ_unchecked::<true>(start).1 };
+                if end > range_end {
+                    self.query_stats.borrow_mut().bad_ranges += 1;
+                    continue;
+                }
+
+                if !verified.insert(start) {
+                    continue;
+                }
+
+                self.query_stats.borrow_mut().approx_candidates += 1;
+                let candidate = self.seq.slice(start..end);
+                let mut mismatches = 0u32;
+                for i in 0..pattern.len() {
+                    if candidate.get(i) != pattern.get(i) {
+                        mismatches += 1;
+                        if mismatches > max_mismatches {
+                            break;
+                        }
+                    }
+                }
+                if mismatches > max_mismatches {
+                    self.query_stats.borrow_mut().mismatches += 1;
+                    continue;
+                }
+                seen.insert(start, mismatches);
+            }
+
+            chunk_start += chunk_len;
+        }
+
+        if !any_chunk_sketched {
+            return None;
+        }
+
+        let start_len = out.len();
+        for (pos, mismatches) in seen {
+            let mut stats = self.query_stats.borrow_mut();
+            stats.matches += 1;
+            *stats.approx_mismatch_histogram.entry(mismatches).or_insert(0) += 1;
+            drop(stats);
+            out.push((pos, mismatches));
+        }
+        Some(out.len() - start_len)
+    }
+
+    /// Like [`Self::query_approx_into`], but returns a freshly allocated
+    /// `Vec` instead of appending to a caller-owned buffer.
+    pub fn query_approx(
+        &self,
+        pattern: SV::Seq<'_>,
+        max_mismatches: u32,
+    ) -> Option<Vec<(usize, u32)>> {
+        let mut out = Vec::new();
+        self.query_approx_into(pattern, max_mismatches, &mut out)?;
+        Some(out)
+    }
+
+    /// Convert a global match position (as returned by [`Self::query`])
+    /// into `(read_index, offset_in_read)`: the ordinal index, among the
+    /// ranges passed to [`Self::build_with_ranges`], of the input
+    /// read/record `pos` falls in, and `pos`'s offset within it.
+    ///
+    /// `self.ranges` stores each range as a `(start, end)` pair of
+    /// consecutive entries, in increasing order, so the read containing
+    /// `pos` is the one whose `end` is the smallest stored value `> pos`.
+    /// `succ_unchecked` already finds that value for the `end > range_end`
+    /// check in [`Self::query`]/[`Self::query_into`]; this additionally
+    /// keeps its *rank* (the `end`'s index among all `2 * num_reads`
+    /// stored entries), which is always odd, so dividing it by two gives
+    /// the read index, and looking up the preceding (even) entry gives
+    /// that read's start.
+    fn pos_to_read_offset(&self, pos: usize) -> (usize, usize) {
+        let (rank, range_end) = unsafe { self.ranges.succ_unchecked::<true>(pos) };
+        debug_assert!(pos < range_end, "pos {pos} is not inside any input range");
+        let read_index = rank / 2;
+        let range_start = self.ranges.get(rank - 1);
+        (read_index, pos - range_start)
+    }
+
+    /// Like [`Self::query`], but yields `(read_index, offset_in_read)`
+    /// instead of a single global offset, by mapping each match through
+    /// [`Self::pos_to_read_offset`]. Useful when `self.seq` is the
+    /// concatenation of many reads/chromosomes (via
+    /// [`Self::build_with_ranges`]) and callers want per-read coordinates
+    /// directly instead of re-deriving them from the global offset.
+    pub fn query_reads<'p>(
+        &'p self,
+        pattern: SV::Seq<'p>,
+    ) -> Option<Box<dyn Iterator<Item = (usize, usize)> + 'p>> {
+        Some(Box::new(
+            self.query(pattern)?
+                .map(move |pos| self.pos_to_read_offset(pos)),
+        ))
+    }
+
+    /// Like [`Self::query_into`], but appends `(read_index,
+    /// offset_in_read)` pairs (see [`Self::query_reads`]) instead of
+    /// global offsets.
+    pub fn query_reads_into<'p>(
+        &'p self,
+        pattern: SV::Seq<'p>,
+        out: &mut Vec<(usize, usize)>,
+    ) -> Option<usize> {
+        let start_len = out.len();
+        let mut positions = Vec::new();
+        let n = self.query_into(pattern, &mut positions)?;
+        out.extend(positions.into_iter().map(|pos| self.pos_to_read_offset(pos)));
+        debug_assert_eq!(out.len() - start_len, n);
+        Some(n)
+    }
+
+    /// Like [`Self::query_into`], but pulls the pattern from `reader` in
+    /// bounded `chunk_size`-byte reads and sketches it incrementally as
+    /// bytes arrive, instead of requiring the caller to have it (or its
+    /// sketch) fully materialized up front (e.g. a pattern streamed
+    /// straight off disk).
+    ///
+    /// A minimizer's window can straddle a chunk boundary, so each newly
+    /// read chunk is sketched together with the last `l - 1` bytes of the
+    /// previous one (`l` = [`Sketcher::l`]) via
+    /// [`Sketcher::sketch_with_positions`]; only minimizers whose window
+    /// lies entirely before that trailing `l - 1`-byte margin are final —
+    /// later bytes can't change them — so those are appended to the
+    /// running minimizer-space pattern immediately and the rest are left
+    /// for the next round. This keeps the sketching step's working set
+    /// bounded by `chunk_size + l` instead of the whole pattern, however
+    /// long it is.
+    ///
+    /// The final byte-level verification against the indexed sequence
+    /// still needs the complete plain-text pattern (to rule out
+    /// minimizer-space collisions), so — unlike the sketching step — that
+    /// part isn't bounded: the full pattern read from `reader` is kept
+    /// around for it, same as [`Self::query_into`] would need if handed
+    /// the fully materialized pattern directly.
+    pub fn query_from_reader(
+        &self,
+        mut reader: impl Read,
+        chunk_size: usize,
+    ) -> io::Result<Vec<usize>> {
+        self.query_stats.borrow_mut().queries += 1;
+        let l = self.sketcher.l();
+        let width = self.sketcher.width();
+        let margin = l.saturating_sub(1);
+
+        // The full pattern, kept around only for the final byte-level
+        // verification below; `window` is the bounded buffer actually
+        // handed to `sketch_with_positions`.
+        let mut pattern = Vec::new();
+        let mut window = Vec::new();
+        let mut window_base = 0; // plain position of `window[0]` within `pattern`.
+        let mut ms_pattern = Vec::new();
+        let mut ms_offset = None;
+
+        let mut buf = vec![0u8; chunk_size];
+        loop {
+            let n = reader.read(&mut buf)?;
+            let eof = n == 0;
+            if !eof {
+                pattern.extend_from_slice(&buf[..n]);
+                window.extend_from_slice(&buf[..n]);
+            }
+            if window.is_empty() {
+                if eof {
+                    break;
+                }
+                continue;
+            }
+            // Not yet enough trailing context to finalize anything; keep reading.
+            if !eof && window.len() <= margin {
+                continue;
+            }
+
+            let mut sv = SV::default();
+            sv.push_ascii(&window);
+            match self.sketcher.sketch_with_positions(sv.as_slice()) {
+                Ok((ms, positions)) => {
+                    debug_assert_eq!(ms.0.len(), positions.len() * width);
+                    let finalized = if eof { window.len() } else { window.len() - margin };
+                    let num_final = positions.iter().take_while(|&&p| p < finalized).count();
+
+                    if ms_offset.is_none() && num_final > 0 {
+                        ms_offset = Some(window_base + positions[0]);
+                    }
+                    ms_pattern.extend_from_slice(&ms.0[..num_final * width]);
+
+                    let drop_to = positions.get(num_final).copied().unwrap_or(finalized);
+                    window_base += drop_to;
+                    window.drain(..drop_to);
+                }
+                Err(SketchError::TooShort) => {
+                    // `window` alone doesn't yet contain a minimizer; keep reading.
+                }
+                Err(SketchError::UnknownMinimizer) => {
+                    self.query_stats.borrow_mut().unknown_minimizer += 1;
+                    return Ok(Vec::new());
+                }
+            }
+
+            if eof {
+                break;
+            }
+        }
+
+        let Some(offset) = ms_offset else {
+            self.query_stats.borrow_mut().too_short += 1;
+            return Ok(Vec::new());
+        };
+
+        let mut sv = SV::default();
+        sv.push_ascii(&pattern);
+        let seq = sv.as_slice();
+
+        let mut ms_occ = Vec::new();
+        self.ms_index.query_into(&ms_pattern, self.seq.as_slice(), &*self.sketcher, &mut ms_occ);
+
+        let mut out = Vec::new();
+        for ms_pos in ms_occ {
+            let Some(plain_pos) = self.sketcher.ms_pos_to_plain_pos(ms_pos) else {
+                self.query_stats.borrow_mut().misaligned_ms_pos += 1;
+                continue;
+            };
+            let Some(start) = plain_pos.checked_sub(offset) else {
+                self.query_stats.borrow_mut().out_of_bounds += 1;
+                continue;
+            };
+            let end = start + seq.len();
+            if end > self.seq.len() {
+                self.query_stats.borrow_mut().out_of_bounds += 1;
+                continue;
+            }
+            if self.seq.slice(start..end) != seq {
+                self.query_stats.borrow_mut().mismatches += 1;
+                continue;
+            }
+            let range_end = unsafe { self.ranges.succ_unchecked::<true>(start).1 };
+            if end > range_end {
+                self.query_stats.borrow_mut().bad_ranges += 1;
+                continue;
+            }
+            let mut stats = self.query_stats.borrow_mut();
+            stats.matches += 1;
+            stats.forward_matches += 1;
+            drop(stats);
+            out.push(start);
+        }
+        Ok(out)
+    }
+
+    /// Like [`Self::query_into`] run once per pattern in `patterns`, but
+    /// distributed across a rayon thread pool instead of run serially —
+    /// the natural path to high-throughput read mapping, where millions of
+    /// short queries are issued against one index. `self.seq`,
+    /// `self.sketcher` and `self.ms_index` are read-only during querying
+    /// and safely `Sync`, but `query_stats` is a `RefCell`, so sharing
+    /// `&self` across workers that each call `query_into` directly would
+    /// race on it. Instead each pattern is answered through
+    /// [`query_positions_with_stats`] with a thread-local `QueryStats`, and
+    /// every one of those is merged into `self.query_stats` once, after the
+    /// parallel portion has finished.
+    pub fn query_batch(&self, patterns: &[SV::Seq<'_>]) -> Vec<Vec<usize>>
+    where
+        SV: Sync,
+    {
+        let sketcher = &*self.sketcher;
+        let ms_index = &*self.ms_index;
+        let ranges = &self.ranges;
+        let seq = self.seq;
+
+        let results: Vec<(Vec<usize>, QueryStats)> = patterns
+            .par_iter()
+            .map(|&pattern| query_positions_with_stats(sketcher, ms_index, ranges, seq, pattern))
+            .collect();
+
+        let mut stats = self.query_stats.borrow_mut();
+        let mut out = Vec::with_capacity(results.len());
+        for (positions, local_stats) in results {
+            stats.merge(&local_stats);
+            out.push(positions);
+        }
+        out
+    }
+
+    /// Like [`Self::query_batch`], but appends each pattern's match
+    /// positions into the correspondingly-indexed, caller-owned slot of
+    /// `out` instead of allocating a fresh `Vec<Vec<_>>` per call, so the
+    /// per-pattern buffers can be reused across many batches.
+    pub fn query_batch_into(&self, patterns: &[SV::Seq<'_>], out: &mut Vec<Vec<usize>>)
+    where
+        SV: Sync,
+    {
+        out.resize_with(patterns.len(), Vec::new);
+
+        let sketcher = &*self.sketcher;
+        let ms_index = &*self.ms_index;
+        let ranges = &self.ranges;
+        let seq = self.seq;
+
+        let local_stats: Vec<QueryStats> = patterns
+            .par_iter()
+            .zip(out.par_iter_mut())
+            .map(|(&pattern, slot)| {
+                let (positions, local_stats) =
+                    query_positions_with_stats(sketcher, ms_index, ranges, seq, pattern);
+                *slot = positions;
+                local_stats
+            })
+            .collect();
+
+        let mut stats = self.query_stats.borrow_mut();
+        for local_stats in local_stats {
+            stats.merge(&local_stats);
+        }
+    }
+
+    /// Like [`Self::query_into`] run once per query in `queries`, but
+    /// partitioned across `num_threads` threads instead of run serially, so
+    /// throughput and latency reflect realistic multicore query cost.
+    /// Bypasses `query_stats` bookkeeping (see [`query_match_count`]); use
+    /// the single-threaded path (e.g. [`Self::bench`] in `bench.rs`) when
+    /// `rss`/correctness instrumentation matters instead of throughput.
+    pub fn bench_parallel(&self, queries: &[SV], num_threads: usize) -> QueryThroughput
+    where
+        SV: Sync,
+    {
+        let num_threads = num_threads.max(1);
+        let sketcher = &*self.sketcher;
+        let ms_index = &*self.ms_index;
+        let ranges = &self.ranges;
+        let seq = self.seq;
+
+        let chunk_size = queries.len().div_ceil(num_threads).max(1);
+        let start = std::time::Instant::now();
+        let latencies: Vec<std::time::Duration> = std::thread::scope(|scope| {
+            queries
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        let mut latencies = Vec::with_capacity(chunk.len());
+                        for q in chunk {
+                            let t0 = std::time::Instant::now();
+                            std::hint::black_box(query_match_count(
+                                sketcher,
+                                ms_index,
+                                ranges,
+                                seq,
+                                q.as_slice(),
+                            ));
+                            latencies.push(t0.elapsed());
+                        }
+                        latencies
+                    })
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect()
+        });
+        let elapsed = start.elapsed();
+
+        QueryThroughput::from_latencies(queries.len(), elapsed, latencies)
+    }
+
+    /// Persist the sketcher, minimizer-space index, and read ranges to
+    /// `dir` (one file per component) behind a small manifest (magic,
+    /// version, `type_name::<SV>()`, the sketcher's `k`/`l`/`width`, and the
+    /// sketcher/index [`Sketcher::type_tag`]/[`Index::type_tag`]s), so a
+    /// later run can reload them via [`Self::load`] instead of re-sketching
+    /// and re-indexing `seq`; a mismatched sequence type or `sketch_params`/
+    /// `index_params` kind is rejected at load time rather than silently
+    /// producing garbage. Returns an error when the sketcher or index does
+    /// not support persistence. The `ranges.bin` file is read/written via
+    /// `sux`'s `epserde` support behind the `serde` feature, falling back to
+    /// a small hand-rolled format otherwise, to keep the default dependency
+    /// set small.
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        self.write_manifest(dir)?;
+        self.sketcher.save(&dir.join("sketcher.bin"))?;
+        self.ms_index.save(&dir.join("index.bin"))?;
+        self.save_ranges(dir)
+    }
+
+    #[cfg(feature = "serde")]
+    fn save_ranges(&self, dir: &Path) -> io::Result<()> {
+        use epserde::ser::Serialize;
+        let mut w = io::BufWriter::new(std::fs::File::create(dir.join("ranges.bin"))?);
+        self.ranges
+            .serialize(&mut w)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "serde"))]
+    fn save_ranges(&self, dir: &Path) -> io::Result<()> {
+        let mut w = io::BufWriter::new(std::fs::File::create(dir.join("ranges.bin"))?);
+        let len = self.ranges.len();
+        w.write_all(&(len as u64).to_le_bytes())?;
+        for i in 0..len {
+            w.write_all(&(self.ranges.get(i) as u64).to_le_bytes())?;
+        }
+        w.flush()
+    }
+
+    fn write_manifest(&self, dir: &Path) -> io::Result<()> {
+        let mut w = io::BufWriter::new(std::fs::File::create(dir.join("manifest.bin"))?);
+        w.write_all(MANIFEST_MAGIC)?;
+        w.write_all(&MANIFEST_VERSION.to_le_bytes())?;
+        let type_name = std::any::type_name::<SV>();
+        w.write_all(&(type_name.len() as u64).to_le_bytes())?;
+        w.write_all(type_name.as_bytes())?;
+        w.write_all(&(self.sketcher.k() as u64).to_le_bytes())?;
+        w.write_all(&(self.sketcher.l() as u64).to_le_bytes())?;
+        w.write_all(&(Sketcher::width(&*self.sketcher) as u64).to_le_bytes())?;
+        Self::write_tag(&mut w, self.sketcher.type_tag())?;
+        Self::write_tag(&mut w, self.ms_index.type_tag())?;
+        w.flush()
+    }
+
+    fn write_tag(w: &mut impl Write, tag: &str) -> io::Result<()> {
+        w.write_all(&(tag.len() as u64).to_le_bytes())?;
+        w.write_all(tag.as_bytes())
+    }
+
+    fn read_tag(r: &mut impl Read) -> io::Result<String> {
+        let mut buf8 = [0u8; 8];
+        r.read_exact(&mut buf8)?;
+        let len = u64::from_le_bytes(buf8) as usize;
+        let mut buf = vec![0u8; len];
+        r.read_exact(&mut buf)?;
+        String::from_utf8(buf)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    /// Read back and validate the manifest written by [`Self::write_manifest`],
+    /// returning the saved sketcher `width` (so the caller can pass it on to
+    /// [`IndexBuilder::load`]/[`IndexBuilder::load_mmap`]) plus the recorded
+    /// sketcher/index [`Sketcher::type_tag`]/[`Index::type_tag`]s, so
+    /// [`Self::load`]/[`Self::load_mmap`] can check the caller passed a
+    /// matching `sketch_params`/`index_params`.
+    fn read_manifest(dir: &Path) -> io::Result<ManifestInfo> {
+        let mut r = io::BufReader::new(std::fs::File::open(dir.join("manifest.bin"))?);
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MANIFEST_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a UIndex directory (manifest magic mismatch)",
+            ));
+        }
+        let mut buf4 = [0u8; 4];
+        r.read_exact(&mut buf4)?;
+        let version = u32::from_le_bytes(buf4);
+        if version != MANIFEST_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported UIndex manifest version {version}"),
+            ));
+        }
+        let mut buf8 = [0u8; 8];
+        r.read_exact(&mut buf8)?;
+        let name_len = u64::from_le_bytes(buf8) as usize;
+        let mut name = vec![0u8; name_len];
+        r.read_exact(&mut name)?;
+        let expected = std::any::type_name::<SV>();
+        if name != expected.as_bytes() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "UIndex was saved for sequence type {:?}, but this is {expected:?}",
+                    String::from_utf8_lossy(&name)
+                ),
+            ));
+        }
+        // k and l are informational only (the caller's sketch_params decides
+        // how to interpret the saved bytes); only width is needed to load
+        // the index.
+        r.read_exact(&mut buf8)?; // k
+        r.read_exact(&mut buf8)?; // l
+        r.read_exact(&mut buf8)?;
+        let width = u64::from_le_bytes(buf8) as usize;
+        let sketcher_tag = Self::read_tag(&mut r)?;
+        let index_tag = Self::read_tag(&mut r)?;
+        Ok(ManifestInfo {
+            width,
+            sketcher_tag,
+            index_tag,
+        })
+    }
+
+    /// Reload a `UIndex` previously written by [`Self::save`] from `dir`,
+    /// against the given `seq` (which the caller is responsible for
+    /// providing, e.g. by re-reading or re-mapping the original input).
+    /// `sketch_params`/`index_params` must be of the same kind used to
+    /// build the saved index, so that [`SketcherBuilder::load`] and
+    /// [`IndexBuilder::load`] know how to reconstruct them.
+    pub fn load(
+        dir: &Path,
+        seq: &'s SV,
+        sketch_params: &dyn SketcherBuilder<SV>,
+        index_params: &dyn IndexBuilder<SV>,
+    ) -> io::Result<Self> {
+        let manifest = Self::read_manifest(dir)?;
+        let sketcher = sketch_params.load(&dir.join("sketcher.bin"))?;
+        Self::check_tag("sketcher", sketcher.type_tag(), &manifest.sketcher_tag)?;
+        let width = Sketcher::width(&*sketcher);
+        debug_assert_eq!(width, manifest.width, "loaded sketcher width mismatches manifest");
+        let ms_index = index_params.load(&dir.join("index.bin"), width)?;
+        Self::check_tag("index", ms_index.type_tag(), &manifest.index_tag)?;
+        Self::from_parts(dir, seq, sketcher, ms_index)
+    }
+
+    /// Like [`Self::load`], but memory-maps the minimizer-space index
+    /// instead of copying it into the heap, so `max_rss()` reflects
+    /// resident-only pages for indices that support it (see
+    /// [`IndexBuilder::load_mmap`]).
+    pub fn load_mmap(
+        dir: &Path,
+        seq: &'s SV,
+        sketch_params: &dyn SketcherBuilder<SV>,
+        index_params: &dyn IndexBuilder<SV>,
+    ) -> io::Result<Self> {
+        let manifest = Self::read_manifest(dir)?;
+        let sketcher = sketch_params.load(&dir.join("sketcher.bin"))?;
+        Self::check_tag("sketcher", sketcher.type_tag(), &manifest.sketcher_tag)?;
+        let width = Sketcher::width(&*sketcher);
+        debug_assert_eq!(width, manifest.width, "loaded sketcher width mismatches manifest");
+        let ms_index = index_params.load_mmap(&dir.join("index.bin"), width)?;
+        Self::check_tag("index", ms_index.type_tag(), &manifest.index_tag)?;
+        Self::from_parts(dir, seq, sketcher, ms_index)
+    }
+
+    /// Compare a just-`load`ed component's [`Index::type_tag`]/
+    /// [`Sketcher::type_tag`] against the one recorded at save time,
+    /// rejecting a `sketch_params`/`index_params` of the wrong concrete kind
+    /// instead of returning a `Self` built from mismatched bytes.
+    fn check_tag(what: &str, actual: &'static str, expected: &str) -> io::Result<()> {
+        if actual != expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("UIndex was saved with {what} kind {expected:?}, but the passed-in {what} is {actual:?}"),
+            ));
+        }
+        Ok(())
+    }
+
+    fn from_parts(
+        dir: &Path,
+        seq: &'s SV,
+        sketcher: Box<dyn Sketcher<SV>>,
+        ms_index: Box<dyn Index<SV>>,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            seq,
+            sketcher,
+            ms_index,
+            query_stats: RefCell::new(QueryStats::default()),
+            stats: Stats::default(),
+            ranges: Self::load_ranges(dir)?,
+        })
+    }
+
+    #[cfg(feature = "serde")]
+    fn load_ranges(dir: &Path) -> io::Result<sux::dict::elias_fano::EfDict> {
+        use epserde::deser::Deserialize;
+        let mut r = io::BufReader::new(std::fs::File::open(dir.join("ranges.bin"))?);
+        sux::dict::elias_fano::EfDict::deserialize_full(&mut r)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    #[cfg(not(feature = "serde"))]
+    fn load_ranges(dir: &Path) -> io::Result<sux::dict::elias_fano::EfDict> {
+        let mut r = io::BufReader::new(std::fs::File::open(dir.join("ranges.bin"))?);
+        let mut buf8 = [0u8; 8];
+        r.read_exact(&mut buf8)?;
+        let len = u64::from_le_bytes(buf8) as usize;
+        let mut values = Vec::with_capacity(len);
+        for _ in 0..len {
+            r.read_exact(&mut buf8)?;
+            values.push(u64::from_le_bytes(buf8) as usize);
+        }
+        let mut ef_ranges =
+            sux::dict::elias_fano::EliasFanoBuilder::new(len, *values.last().unwrap_or(&0));
+        for v in values {
+            ef_ranges.push(v);
+        }
+        Ok(ef_ranges.build_with_dict())
+    }
+}
+
+/// Parsed contents of a [`UIndex::save`]d manifest, returned by
+/// [`UIndex::read_manifest`].
+struct ManifestInfo {
+    width: usize,
+    sketcher_tag: String,
+    index_tag: String,
 }
@@ -1,9 +1,13 @@
 use std::{
-    cell::Cell,
+    cell::{Cell, RefCell},
     collections::HashMap,
     ops::Range,
     path::Path,
-    sync::{LazyLock, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, LazyLock, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
 use mem_dbg::{MemSize, SizeFlags};
@@ -13,8 +17,27 @@ use tracing::{info, trace};
 
 thread_local! {
     static TIMER_DEPTH: Cell<usize> = Cell::new(0);
+    /// Names of all `Timer`s currently on this thread's stack, outermost
+    /// first, so a completed `Timer` can record which call path it ran
+    /// under (see `TIMER_SAMPLES`/`timer_profile_folded`/`timer_profile_dot`).
+    static TIMER_STACK: RefCell<Vec<&'static str>> = RefCell::new(Vec::new());
 }
 
+/// The phase label most recently set by `Timer::new_stats`/`Timer::next`,
+/// i.e. the current benchmark phase. Plain `Timer::new` does not set it,
+/// and dropping a `Timer` does not restore the previous label. Deliberately
+/// *not* thread-local: `ResourceSampler` reads this from its own background
+/// thread, so a per-thread cell (only ever written on the thread that owns
+/// the `Timer`) would never see an update and every sample would be tagged
+/// with the empty string.
+static CURRENT_PHASE: Mutex<&'static str> = Mutex::new("");
+
+/// Every `(stack_path, elapsed)` sample recorded by a `Timer` on `next` or drop,
+/// across all threads, since the process started. Powers the
+/// `timer_profile_folded`/`timer_profile_dot` exporters.
+static TIMER_SAMPLES: LazyLock<Mutex<Vec<(Vec<&'static str>, Duration)>>> =
+    LazyLock::new(|| Mutex::new(Vec::new()));
+
 pub struct Timer<'s> {
     name: &'static str,
     stats: Option<&'s Stats>,
@@ -27,6 +50,7 @@ impl<'s> Timer<'s> {
     pub fn new(name: &'static str) -> Self {
         let depth = TIMER_DEPTH.with(|d| d.get());
         TIMER_DEPTH.with(|d| d.set(depth + 1));
+        TIMER_STACK.with(|s| s.borrow_mut().push(name));
         Self {
             name,
             stats: None,
@@ -38,6 +62,8 @@ impl<'s> Timer<'s> {
     pub fn new_stats(name: &'static str, stats: &'s Stats) -> Self {
         let depth = TIMER_DEPTH.with(|d| d.get());
         TIMER_DEPTH.with(|d| d.set(depth + 1));
+        TIMER_STACK.with(|s| s.borrow_mut().push(name));
+        *CURRENT_PHASE.lock().unwrap() = name;
         Self {
             name,
             stats: Some(stats),
@@ -54,6 +80,12 @@ impl<'s> Timer<'s> {
         self.log();
         self.name = name;
         self.start = std::time::Instant::now();
+        *CURRENT_PHASE.lock().unwrap() = name;
+        TIMER_STACK.with(|s| {
+            if let Some(last) = s.borrow_mut().last_mut() {
+                *last = name;
+            }
+        });
     }
 
     fn log(&self) {
@@ -61,6 +93,8 @@ impl<'s> Timer<'s> {
         if let Some(stats) = self.stats {
             stats.add(self.name, elapsed.as_secs_f32());
         }
+        let path = TIMER_STACK.with(|s| s.borrow().clone());
+        TIMER_SAMPLES.lock().unwrap().push((path, elapsed));
         let mut prefix = String::new();
         for _ in 0..self.depth {
             prefix.push_str(" ");
@@ -76,10 +110,76 @@ impl<'s> Timer<'s> {
 impl<'s> Drop for Timer<'s> {
     fn drop(&mut self) {
         self.log();
+        TIMER_STACK.with(|s| {
+            s.borrow_mut().pop();
+        });
         TIMER_DEPTH.with(|d| d.set(d.get() - 1));
     }
 }
 
+/// Render every `Timer` sample recorded so far (process-wide, across all
+/// threads) as folded-stack lines (`a;b;c <micros>`), aggregating repeated
+/// calls under the same stack path into one line. Consumable by standard
+/// flamegraph tooling (e.g. `inferno-flamegraph`).
+pub fn timer_profile_folded() -> String {
+    let samples = TIMER_SAMPLES.lock().unwrap();
+    let mut totals: HashMap<&Vec<&'static str>, u128> = HashMap::new();
+    for (path, elapsed) in samples.iter() {
+        *totals.entry(path).or_insert(0) += elapsed.as_micros();
+    }
+    let mut lines = totals
+        .into_iter()
+        .map(|(path, micros)| format!("{} {micros}", path.join(";")))
+        .collect::<Vec<_>>();
+    lines.sort();
+    lines.join("\n")
+}
+
+/// Render every `Timer` sample recorded so far as a Graphviz `digraph`:
+/// nodes are timed regions (the deepest segment of their stack path), and
+/// `->` edges connect a region to its parent, labeled with the total
+/// elapsed time and number of calls summed over that exact stack path.
+pub fn timer_profile_dot() -> String {
+    let samples = TIMER_SAMPLES.lock().unwrap();
+    let mut totals: HashMap<&Vec<&'static str>, (u128, usize)> = HashMap::new();
+    for (path, elapsed) in samples.iter() {
+        let entry = totals.entry(path).or_insert((0, 0));
+        entry.0 += elapsed.as_micros();
+        entry.1 += 1;
+    }
+
+    fn node_id(path: &[&str]) -> String {
+        if path.is_empty() {
+            "root".to_string()
+        } else {
+            format!(
+                "n_{}",
+                path.join("_")
+                    .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+            )
+        }
+    }
+
+    let mut out = String::from("digraph timers {\n  root [label=\"root\"];\n");
+    for path in totals.keys() {
+        out.push_str(&format!(
+            "  {} [label={:?}];\n",
+            node_id(path),
+            path.last().copied().unwrap_or("root")
+        ));
+    }
+    for (path, (micros, count)) in &totals {
+        let parent_id = node_id(&path[..path.len().saturating_sub(1)]);
+        let child_id = node_id(path);
+        out.push_str(&format!(
+            "  {parent_id} -> {child_id} [label=\"{:.3}ms x{count}\"];\n",
+            *micros as f64 / 1000.0
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
 fn init_trace() {
     use tracing::level_filters::LevelFilter;
     use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -136,6 +236,196 @@ impl Stats {
     }
 }
 
+/// Returns accumulated user+system CPU time of the whole process, in seconds.
+fn cpu_time() -> f64 {
+    let rusage = unsafe {
+        let mut rusage = std::mem::MaybeUninit::uninit();
+        libc::getrusage(libc::RUSAGE_SELF, rusage.as_mut_ptr());
+        rusage.assume_init()
+    };
+    let to_secs = |tv: libc::timeval| tv.tv_sec as f64 + tv.tv_usec as f64 / 1_000_000.;
+    to_secs(rusage.ru_utime) + to_secs(rusage.ru_stime)
+}
+
+/// Peak RSS of the whole process so far, in bytes.
+fn max_rss() -> usize {
+    let rusage = unsafe {
+        let mut rusage = std::mem::MaybeUninit::uninit();
+        libc::getrusage(libc::RUSAGE_SELF, rusage.as_mut_ptr());
+        rusage.assume_init()
+    };
+    // On linux, the returned value is in kB.
+    rusage.ru_maxrss as usize * 1024
+}
+
+/// A single `ResourceSampler` data point.
+#[derive(Clone, Debug)]
+pub struct ResourceSample {
+    /// Milliseconds since the sampler was started.
+    pub timestamp_ms: u64,
+    /// The phase label most recently set by `Timer::new_stats`/`Timer::next`
+    /// when this sample was taken.
+    pub phase: &'static str,
+    /// Running-maximum RSS, in bytes.
+    pub rss_bytes: usize,
+    /// CPU usage over the preceding interval, as a percentage (100 = 1 core busy).
+    pub cpu_pct: f64,
+}
+
+/// Guard returned by [`Stats::sample_resources`]. Stops the sampling thread
+/// and joins it on drop, then writes the collected series into the `Stats`
+/// that spawned it under the `"resource_samples"` key.
+pub struct ResourceSamplerGuard<'s> {
+    stop: Arc<AtomicBool>,
+    handle: Option<std::thread::JoinHandle<Vec<ResourceSample>>>,
+    stats: &'s Stats,
+}
+
+impl<'s> Drop for ResourceSamplerGuard<'s> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let samples = self.handle.take().unwrap().join().unwrap();
+        let samples = samples
+            .into_iter()
+            .map(|s| {
+                Value::Array(vec![
+                    Value::Number(Number::from(s.timestamp_ms)),
+                    Value::String(s.phase.to_string()),
+                    Value::Number(Number::from(s.rss_bytes)),
+                    Value::Number(Number::from_f64(s.cpu_pct).unwrap()),
+                ])
+            })
+            .collect();
+        self.stats.set_val("resource_samples", Value::Array(samples));
+    }
+}
+
+impl Stats {
+    /// Starts a background thread that samples `getrusage`'s peak RSS and
+    /// accumulated CPU time every `interval`, tagging each sample with the
+    /// phase label most recently set by `Timer::new_stats`/`Timer::next`. Sampling stops, and the
+    /// thread is joined, when the returned guard is dropped, so no samples
+    /// are lost: `let _g = stats.sample_resources(interval);`.
+    pub fn sample_resources(&self, interval: Duration) -> ResourceSamplerGuard<'_> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop2 = stop.clone();
+        let handle = std::thread::spawn(move || {
+            let start = Instant::now();
+            let mut samples = Vec::new();
+            let mut last_cpu = cpu_time();
+            let mut last_t = start;
+            let mut peak_rss = 0usize;
+            while !stop2.load(Ordering::Relaxed) {
+                std::thread::sleep(interval);
+                let now = Instant::now();
+                peak_rss = peak_rss.max(max_rss());
+                let cpu = cpu_time();
+                let dt = now.duration_since(last_t).as_secs_f64();
+                let cpu_pct = if dt > 0. { (cpu - last_cpu) / dt * 100. } else { 0. };
+                last_cpu = cpu;
+                last_t = now;
+                samples.push(ResourceSample {
+                    timestamp_ms: now.duration_since(start).as_millis() as u64,
+                    phase: *CURRENT_PHASE.lock().unwrap(),
+                    rss_bytes: peak_rss,
+                    cpu_pct,
+                });
+            }
+            samples
+        });
+        ResourceSamplerGuard {
+            stop,
+            handle: Some(handle),
+            stats: self,
+        }
+    }
+}
+
+/// Write a previously-collected `"resource_samples"` array (see
+/// [`Stats::sample_resources`]) out as `timestamp,phase,rss_bytes,cpu_pct` CSV.
+pub fn write_resource_csv(samples: &Value, path: &Path) -> std::io::Result<()> {
+    let mut out = String::from("timestamp,phase,rss_bytes,cpu_pct\n");
+    if let Some(rows) = samples.as_array() {
+        for row in rows {
+            if let Some([timestamp, phase, rss_bytes, cpu_pct]) = row.as_array().map(|v| v.as_slice()) {
+                out.push_str(&format!(
+                    "{},{},{},{}\n",
+                    timestamp,
+                    phase.as_str().unwrap_or_default(),
+                    rss_bytes,
+                    cpu_pct
+                ));
+            }
+        }
+    }
+    std::fs::write(path, out)
+}
+
+/// Merge `new_records` into the JSON array at `path`, keyed by each record's
+/// `"id"` field, instead of unconditionally overwriting it. Mirrors
+/// `decomp-toolkit`'s "smarter configuration updates": records sharing an
+/// `id` with an existing entry replace it, others are appended, and if the
+/// serialized result is byte-identical to what is already on disk the write
+/// is skipped entirely. Reads the file's mtime before merging and re-stats
+/// it just before writing, returning an error if it changed in between (some
+/// other worker wrote to it concurrently). The final write goes to a temp
+/// file that is atomically renamed into place, so readers never observe a
+/// partially-written file.
+pub fn write_stats_json(
+    path: &Path,
+    new_records: &[HashMap<&str, Value>],
+) -> std::io::Result<()> {
+    let prev_mtime = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    let mut records: Vec<Value> = match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_default(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+
+    for record in new_records {
+        let id = record.get("id");
+        let existing = records
+            .iter()
+            .position(|r| id.is_some() && r.as_object().and_then(|o| o.get("id")) == id);
+        let value = serde_json::to_value(record).unwrap();
+        match existing {
+            Some(i) => records[i] = value,
+            None => records.push(value),
+        }
+    }
+
+    let serialized = serde_json::to_string(&records).unwrap();
+
+    if let Ok(current) = std::fs::read(path) {
+        if current == serialized.as_bytes() {
+            return Ok(());
+        }
+    }
+
+    let current_mtime = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    if current_mtime != prev_mtime {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            format!("{path:?} changed on disk since it was read; refusing to overwrite it"),
+        ));
+    }
+
+    // A uniquely-named temp file in the same directory, so concurrent
+    // workers calling this on the same `path` never write each other's
+    // bytes into a shared `.tmp` file before the rename.
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = tempfile::Builder::new()
+        .prefix(&format!(".{file_name}."))
+        .suffix(".tmp")
+        .tempfile_in(dir)?;
+    std::fs::write(tmp.path(), serialized)?;
+    tmp.persist(path)
+        .map_err(|e| e.error)?;
+    Ok(())
+}
+
 pub fn read_fastq<SV: SeqVec>(path: &Path) -> Vec<SV> {
     *INIT_TRACE;
     let _timer = Timer::new("Reading");